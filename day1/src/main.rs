@@ -1,23 +1,722 @@
-use itertools::Itertools;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    num::ParseIntError,
+    path::PathBuf,
+};
+
+// the sum every expense report entry combination is checked against
+const TARGET: i64 = 2020;
+
+// A single expense report entry, along with the (1-based) line it was read from
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Entry {
+    line: usize,
+    value: i64,
+}
+
+// What the binary should compute from the expense report
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Mode {
+    // fixed-size combinations (parts 1 and 2)
+    Tuples,
+    // subsets of any size
+    Subsets,
+    // pairs and triples, reported while the input is still being read
+    Stream,
+}
+
+// Command line options: `day1 [tuples|subsets|stream] [--wide] [PATH]`
+#[derive(Clone, PartialEq, Debug)]
+struct Options {
+    mode: Mode,
+    // compute products as i128 instead of i64
+    wide: bool,
+    // where to read the expense report from, stdin if None or "-"
+    path: Option<PathBuf>,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+enum Error {
+    #[error("product of {terms:?} overflows {width}")]
+    Overflow { terms: Vec<i64>, width: &'static str },
+
+    #[error("line {line}: could not parse {text:?} as an expense")]
+    Parse {
+        line: usize,
+        text: String,
+        source: ParseIntError,
+    },
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options {
+        mode: Mode::Tuples,
+        wide: false,
+        path: None,
+    };
+    for arg in args {
+        match arg.as_str() {
+            "tuples" => options.mode = Mode::Tuples,
+            "subsets" => options.mode = Mode::Subsets,
+            "stream" => options.mode = Mode::Stream,
+            "--wide" => options.wide = true,
+            flag if flag.starts_with("--") => {
+                anyhow::bail!("unknown flag {:?}, expected '--wide'", flag)
+            }
+            path => {
+                if let Some(prev) = &options.path {
+                    anyhow::bail!("more than one input path: {:?} and {:?}", prev, path);
+                }
+                options.path = Some(path.into());
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Parses one expense per line. Surrounding whitespace and blank lines are
+/// ignored, but still count towards the line numbers.
+fn parse_entries(input: &str) -> Result<Vec<Entry>, Error> {
+    input.lines()
+        .enumerate()
+        .filter_map(|(i, s)| parse_entry(i + 1, s))
+        .collect()
+}
+
+/// Like `parse_entries`, but reads the lines one at a time as they're needed.
+fn read_entries(reader: impl BufRead) -> impl Iterator<Item = anyhow::Result<Entry>> {
+    reader.lines()
+        .enumerate()
+        .filter_map(|(i, s)| match s {
+            Ok(s) => parse_entry(i + 1, &s).map(|res| res.map_err(Into::into)),
+            Err(e) => Some(Err(e.into())),
+        })
+}
+
+// returns None for blank lines
+fn parse_entry(line: usize, s: &str) -> Option<Result<Entry, Error>> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| {
+        s.parse()
+            .map(|value| Entry { line, value })
+            .map_err(|source| Error::Parse { line, text: s.into(), source })
+    })
+}
 
 fn main() -> anyhow::Result<()> {
-    // let s = std::fs::read_to_string("./src/input.txt")?;
-    let pair = find_a_pair(include_str!("input.txt").lines()
-                                   .map(str::parse::<i64>)
-                                   .collect::<Result<Vec<_>, _>>()?,
-    );
-    // dbg!(&s[..20]);
+    let options = parse_args(std::env::args().skip(1))?;
+
+    let mut reader: Box<dyn BufRead> = match options.path.as_deref() {
+        Some(path) if path.as_os_str() != "-" => Box::new(BufReader::new(
+            File::open(path).map_err(|e| anyhow::anyhow!("could not read {:?}: {}", path, e))?,
+        )),
+        _ => Box::new(io::stdin().lock()),
+    };
+
+    if options.mode == Mode::Stream {
+        return report_stream(reader, options.wide);
+    }
+
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let entries = parse_entries(&input)?;
+
+    match options.mode {
+        Mode::Tuples => report_tuples(&entries, options.wide)?,
+        Mode::Subsets => report_subsets(&entries)?,
+        Mode::Stream => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn report_tuples(entries: &[Entry], wide: bool) -> anyhow::Result<()> {
+    let values: Vec<i64> = entries.iter().map(|e| e.value).collect();
+
+    // part 1 looks for a pair, part 2 for a triple
+    for (part, k) in [(1, 2), (2, 3)] {
+        match find_k_sum(&values, k, TARGET) {
+            Some(terms) => println!("Part {}: {:?} -> {}", part, terms, product(&terms, wide)?),
+            None => println!("Part {}: no {} entries sum up to {}", part, k, TARGET),
+        }
 
-    dbg!(pair.into_iter().map(|(a, b, c)| a*b*c).collect::<Vec<i64>>());
+        // list every combination with its origin, so the answer can be audited
+        for combination in find_all_k_sums(entries, k, TARGET) {
+            println!("  {}", format_terms(&combination));
+        }
+    }
+    Ok(())
+}
 
-    // println!("Hello, world!");
+fn report_stream(reader: impl BufRead, wide: bool) -> anyhow::Result<()> {
+    let mut sums = StreamingSums::new(TARGET);
+    for entry in read_entries(reader) {
+        for found in sums.push(entry?) {
+            let terms: Vec<i64> = found.entries().iter().map(|e| e.value).collect();
+            println!("{}: {} -> {}", found.name(), format_terms(found.entries()), product(&terms, wide)?);
+        }
+        if sums.is_done() {
+            break;
+        }
+    }
+    if sums.pair.is_none() {
+        println!("No pair sums up to {}", TARGET);
+    }
+    if sums.triple.is_none() {
+        println!("No triple sums up to {}", TARGET);
+    }
+    Ok(())
+}
 
+fn report_subsets(entries: &[Entry]) -> anyhow::Result<()> {
+    let sums = subset_sums(entries, TARGET)?;
+    println!("{} subsets sum up to {}", sums.count, TARGET);
+    match sums.smallest {
+        Some(subset) => println!(
+            "Smallest ({} entries): {}", subset.len(), format_terms(&subset)
+        ),
+        None => println!("No subset sums up to {}", TARGET),
+    }
     Ok(())
 }
 
+fn format_terms(entries: &[Entry]) -> String {
+    entries.iter()
+        .map(|e| format!("{} (line {})", e.value, e.line))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+// the product as printed in reports, 128 bits wide if asked to
+fn product(terms: &[i64], wide: bool) -> anyhow::Result<i128> {
+    if wide {
+        Ok(checked_wide_product(terms)?)
+    } else {
+        Ok(checked_product(terms).map_err(|e| anyhow::anyhow!("{} (try --wide)", e))?.into())
+    }
+}
+
+/// Multiplies `terms` together, failing instead of wrapping around on overflow.
+fn checked_product(terms: &[i64]) -> Result<i64, Error> {
+    terms.iter()
+        .try_fold(1i64, |acc, &t| acc.checked_mul(t))
+        .ok_or_else(|| Error::Overflow { terms: terms.to_vec(), width: "i64" })
+}
+
+/// Like `checked_product`, but multiplies in 128 bits, for large values and large `k`.
+fn checked_wide_product(terms: &[i64]) -> Result<i128, Error> {
+    terms.iter()
+        .try_fold(1i128, |acc, &t| acc.checked_mul(t.into()))
+        .ok_or_else(|| Error::Overflow { terms: terms.to_vec(), width: "i128" })
+}
+
+/// Finds `k` entries of `values` (each used at most once) that add up to `target`.
+/// The returned terms are in ascending order.
+fn find_k_sum(values: &[i64], k: usize, target: i64) -> Option<Vec<i64>> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    k_sum_sorted(&sorted, k, target.into())
+}
+
+// fixes the smallest term and recurses until only two terms are left,
+// which are found with a two-pointer sweep: O(n^(k-1)) instead of O(n^k).
+// The remaining target is tracked as i128, so sums of large values can't wrap.
+fn k_sum_sorted(sorted: &[i64], k: usize, target: i128) -> Option<Vec<i64>> {
+    if k > sorted.len() {
+        return None;
+    }
+    match k {
+        0 => (target == 0).then(Vec::new),
+        1 => {
+            let target = i64::try_from(target).ok()?;
+            sorted.binary_search(&target).ok().map(|_| vec![target])
+        }
+        2 => two_sum_sorted(sorted, target).map(|(a, b)| vec![a, b]),
+        _ => {
+            for (i, &first) in sorted.iter().enumerate() {
+                // the same smallest term can't lead to a different outcome twice
+                if i > 0 && sorted[i - 1] == first {
+                    continue;
+                }
+                if let Some(mut terms) = k_sum_sorted(&sorted[i + 1..], k - 1, target - first as i128) {
+                    terms.insert(0, first);
+                    return Some(terms);
+                }
+            }
+            None
+        }
+    }
+}
+
+fn two_sum_sorted(sorted: &[i64], target: i128) -> Option<(i64, i64)> {
+    if sorted.len() < 2 {
+        return None;
+    }
+    let (mut lo, mut hi) = (0, sorted.len() - 1);
+    while lo < hi {
+        let sum = sorted[lo] as i128 + sorted[hi] as i128;
+        match sum.cmp(&target) {
+            std::cmp::Ordering::Less => lo += 1,
+            std::cmp::Ordering::Greater => hi -= 1,
+            std::cmp::Ordering::Equal => return Some((sorted[lo], sorted[hi])),
+        }
+    }
+    None
+}
+
+/// Finds every combination of `k` distinct entries that add up to `target`.
+/// An entry is never reused, but equal values on different lines are distinct
+/// entries. Each combination is listed in ascending value order (ties by line).
+fn find_all_k_sums(entries: &[Entry], k: usize, target: i64) -> Vec<Vec<Entry>> {
+    let mut sorted = entries.to_vec();
+    // stable, so equal values keep their line order
+    sorted.sort_by_key(|e| e.value);
 
-fn find_a_pair(input_vec: Vec<i64>) -> Option<(i64, i64, i64)> {
-    input_vec.into_iter()
-        .tuple_combinations()
-        .find(|(a, b, c)| a + b +c == 2020)
-}
\ No newline at end of file
+    let mut res = Vec::new();
+    all_k_sums_sorted(&sorted, k, target.into(), &mut Vec::with_capacity(k), &mut res);
+    res
+}
+
+fn all_k_sums_sorted(
+    sorted: &[Entry],
+    k: usize,
+    target: i128,
+    prefix: &mut Vec<Entry>,
+    res: &mut Vec<Vec<Entry>>,
+) {
+    if k > sorted.len() {
+        return;
+    }
+    match k {
+        0 => {
+            if target == 0 {
+                res.push(prefix.clone());
+            }
+        }
+        1 => {
+            for &e in sorted.iter().filter(|e| e.value as i128 == target) {
+                res.push(prefix.iter().copied().chain([e]).collect());
+            }
+        }
+        2 => {
+            for (a, b) in all_two_sums_sorted(sorted, target) {
+                res.push(prefix.iter().copied().chain([a, b]).collect());
+            }
+        }
+        _ => {
+            for (i, &first) in sorted.iter().enumerate() {
+                prefix.push(first);
+                all_k_sums_sorted(&sorted[i + 1..], k - 1, target - first.value as i128, prefix, res);
+                prefix.pop();
+            }
+        }
+    }
+}
+
+// two-pointer sweep that expands runs of equal values into every pair they form
+fn all_two_sums_sorted(sorted: &[Entry], target: i128) -> Vec<(Entry, Entry)> {
+    let mut res = Vec::new();
+    if sorted.len() < 2 {
+        return res;
+    }
+    let (mut lo, mut hi) = (0, sorted.len() - 1);
+    while lo < hi {
+        let (a, b) = (sorted[lo].value, sorted[hi].value);
+        match (a as i128 + b as i128).cmp(&target) {
+            std::cmp::Ordering::Less => lo += 1,
+            std::cmp::Ordering::Greater => hi -= 1,
+            std::cmp::Ordering::Equal if a == b => {
+                // everything between the pointers has the same value
+                for i in lo..hi {
+                    for j in i + 1..=hi {
+                        res.push((sorted[i], sorted[j]));
+                    }
+                }
+                break;
+            }
+            std::cmp::Ordering::Equal => {
+                let lo_end = lo + sorted[lo..].iter().take_while(|e| e.value == a).count();
+                let hi_start = hi + 1 - sorted[..=hi].iter().rev().take_while(|e| e.value == b).count();
+                for i in lo..lo_end {
+                    for j in hi_start..=hi {
+                        res.push((sorted[i], sorted[j]));
+                    }
+                }
+                lo = lo_end;
+                hi = hi_start - 1;
+            }
+        }
+    }
+    res
+}
+
+// A combination found by `StreamingSums`
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum StreamMatch {
+    Pair([Entry; 2]),
+    Triple([Entry; 3]),
+}
+
+impl StreamMatch {
+    fn name(&self) -> &'static str {
+        match self {
+            StreamMatch::Pair(_) => "Pair",
+            StreamMatch::Triple(_) => "Triple",
+        }
+    }
+
+    fn entries(&self) -> &[Entry] {
+        match self {
+            StreamMatch::Pair(entries) => entries,
+            StreamMatch::Triple(entries) => entries,
+        }
+    }
+}
+
+/// Online pair and triple detection: entries are pushed one at a time, and each
+/// combination is reported by the push that completes it. Only the first entry
+/// of every value and the first pair of every pairwise sum are remembered.
+struct StreamingSums {
+    target: i64,
+    seen: HashMap<i64, Entry>,
+    pair_sums: HashMap<i128, [Entry; 2]>,
+    pair: Option<[Entry; 2]>,
+    triple: Option<[Entry; 3]>,
+}
+
+impl StreamingSums {
+    fn new(target: i64) -> Self {
+        Self {
+            target,
+            seen: Default::default(),
+            pair_sums: Default::default(),
+            pair: None,
+            triple: None,
+        }
+    }
+
+    /// Returns the combinations that this entry completes, at most one of each kind.
+    fn push(&mut self, entry: Entry) -> Vec<StreamMatch> {
+        let mut res = Vec::new();
+        let rest = self.target as i128 - entry.value as i128;
+
+        if self.pair.is_none() {
+            if let Some(&other) = i64::try_from(rest).ok().and_then(|rest| self.seen.get(&rest)) {
+                self.pair = Some([other, entry]);
+                res.push(StreamMatch::Pair([other, entry]));
+            }
+        }
+
+        if self.triple.is_none() {
+            if let Some(&[a, b]) = self.pair_sums.get(&rest) {
+                self.triple = Some([a, b, entry]);
+                res.push(StreamMatch::Triple([a, b, entry]));
+                // nothing needs the pairwise sums anymore
+                self.pair_sums = Default::default();
+            } else {
+                for &other in self.seen.values() {
+                    self.pair_sums
+                        .entry(other.value as i128 + entry.value as i128)
+                        .or_insert([other, entry]);
+                }
+            }
+        }
+
+        self.seen.entry(entry.value).or_insert(entry);
+        res
+    }
+
+    fn is_done(&self) -> bool {
+        self.pair.is_some() && self.triple.is_some()
+    }
+}
+
+// Everything we know about the subsets (of any size) that add up to a target
+#[derive(PartialEq, Debug)]
+struct SubsetSums {
+    count: u128,
+    smallest: Option<Vec<Entry>>,
+}
+
+/// Counts the subsets of `entries` that add up to `target` and finds one with
+/// the fewest entries, with a 0/1 knapsack over every sum from 0 to `target`.
+/// Only works for non-negative values and targets, since sums are table indices.
+fn subset_sums(entries: &[Entry], target: i64) -> anyhow::Result<SubsetSums> {
+    if target < 0 {
+        anyhow::bail!("subset sums need a non-negative target, got {}", target);
+    }
+    if let Some(e) = entries.iter().find(|e| e.value < 0) {
+        anyhow::bail!("subset sums need non-negative values, got {} on line {}", e.value, e.line);
+    }
+    let target = target as usize;
+
+    // counts[s] is the number of subsets of the entries seen so far that sum up to s
+    let mut counts = vec![0u128; target + 1];
+    counts[0] = 1;
+    // fewest[i][s] is the size of the smallest subset of the first i entries summing
+    // up to s, usize::MAX if there is none; kept per entry to rebuild the subset
+    let mut fewest = vec![vec![usize::MAX; target + 1]];
+    fewest[0][0] = 0;
+
+    for e in entries {
+        let value = e.value as usize;
+        let prev = fewest.last().unwrap();
+        let mut next = prev.clone();
+        // iterate downwards so each entry is used at most once
+        for s in (value..=target).rev() {
+            counts[s] = counts[s].checked_add(counts[s - value])
+                .ok_or_else(|| anyhow::anyhow!("subset count for sum {} overflows u128", s))?;
+            if prev[s - value] != usize::MAX {
+                next[s] = next[s].min(prev[s - value] + 1);
+            }
+        }
+        fewest.push(next);
+    }
+
+    let smallest = (fewest[entries.len()][target] != usize::MAX).then(|| {
+        let mut subset = Vec::new();
+        let mut s = target;
+        for i in (1..=entries.len()).rev() {
+            // entry i - 1 is part of the subset if leaving it out makes it bigger
+            if fewest[i][s] != fewest[i - 1][s] {
+                subset.push(entries[i - 1]);
+                s -= entries[i - 1].value as usize;
+            }
+        }
+        subset.reverse();
+        subset
+    });
+
+    Ok(SubsetSums {
+        count: counts[target],
+        smallest,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Entry, Error, Mode, Options, checked_product, checked_wide_product, find_all_k_sums,
+        StreamMatch, StreamingSums, find_k_sum, parse_args, parse_entries, read_entries,
+        subset_sums};
+
+    fn entries(values: &[i64]) -> Vec<Entry> {
+        values.iter()
+            .enumerate()
+            .map(|(i, &value)| Entry { line: i + 1, value })
+            .collect()
+    }
+
+    fn lines(combinations: Vec<Vec<Entry>>) -> Vec<Vec<usize>> {
+        combinations.into_iter()
+            .map(|c| c.into_iter().map(|e| e.line).collect())
+            .collect()
+    }
+
+    const EXAMPLE: &[i64] = &[1721, 979, 366, 299, 675, 1456];
+
+    #[test]
+    fn test_find_k_sum() {
+        assert_eq!(find_k_sum(EXAMPLE, 2, 2020), Some(vec![299, 1721]), "part 1 example");
+        assert_eq!(find_k_sum(EXAMPLE, 3, 2020), Some(vec![366, 675, 979]), "part 2 example");
+        assert_eq!(find_k_sum(EXAMPLE, 4, 2020), None, "no four entries sum up to 2020");
+        assert_eq!(find_k_sum(EXAMPLE, 1, 979), Some(vec![979]), "a single entry");
+        assert_eq!(find_k_sum(EXAMPLE, 7, 2020), None, "more terms than entries");
+    }
+
+    #[test]
+    fn test_find_k_sum_no_reuse() {
+        assert_eq!(find_k_sum(&[1010], 2, 2020), None, "1010 can't be used twice");
+        assert_eq!(find_k_sum(&[1010, 1010], 2, 2020), Some(vec![1010, 1010]));
+        assert_eq!(find_k_sum(&[5, -3, 10, 0], 3, 2), Some(vec![-3, 0, 5]), "negative values");
+    }
+
+    #[test]
+    fn test_find_all_k_sums() {
+        let example = entries(EXAMPLE);
+        assert_eq!(lines(find_all_k_sums(&example, 2, 2020)), vec![vec![4, 1]], "part 1 example");
+        assert_eq!(lines(find_all_k_sums(&example, 3, 2020)), vec![vec![3, 5, 2]], "part 2 example");
+        assert_eq!(
+            lines(find_all_k_sums(&entries(&[1, 4, 3, 2]), 2, 5)),
+            vec![vec![1, 2], vec![4, 3]],
+            "every pair is reported"
+        );
+        assert_eq!(
+            lines(find_all_k_sums(&entries(&[1, 1, 1]), 3, 3)),
+            vec![vec![1, 2, 3]],
+            "same values on different lines, but no reuse"
+        );
+    }
+
+    #[test]
+    fn test_find_all_k_sums_duplicates() {
+        assert_eq!(
+            lines(find_all_k_sums(&entries(&[1010]), 2, 2020)),
+            Vec::<Vec<usize>>::new(),
+            "a single 1010 can't be used twice"
+        );
+        assert_eq!(
+            lines(find_all_k_sums(&entries(&[1010, 5, 1010, 1010]), 2, 2020)),
+            vec![vec![1, 3], vec![1, 4], vec![3, 4]],
+            "three 1010s make three pairs"
+        );
+        assert_eq!(
+            lines(find_all_k_sums(&entries(&[1, 9, 1, 9, 9]), 2, 10)),
+            vec![vec![1, 2], vec![1, 4], vec![1, 5], vec![3, 2], vec![3, 4], vec![3, 5]],
+            "runs on both sides"
+        );
+    }
+
+    #[test]
+    fn test_subset_sums() {
+        let sums = subset_sums(&entries(EXAMPLE), 2020).unwrap();
+        assert_eq!(sums.count, 2, "the part 1 pair and the part 2 triple");
+        assert_eq!(lines(sums.smallest.into_iter().collect()), vec![vec![1, 4]]);
+
+        let sums = subset_sums(&entries(&[1, 2, 3, 4, 5]), 5).unwrap();
+        assert_eq!(sums.count, 3, "5, 1 + 4 and 2 + 3");
+        assert_eq!(lines(sums.smallest.into_iter().collect()), vec![vec![5]]);
+
+        let sums = subset_sums(&entries(&[2, 4]), 5).unwrap();
+        assert_eq!(sums.count, 0);
+        assert_eq!(sums.smallest, None);
+
+        assert!(subset_sums(&entries(&[1, -1]), 0).is_err(), "negative values");
+    }
+
+    #[test]
+    fn test_subset_sums_large_count() {
+        // every one of the 2^100 subsets of 100 zeros sums up to 0
+        let sums = subset_sums(&entries(&[0; 100]), 0).unwrap();
+        assert_eq!(sums.count, 1 << 100);
+        assert_eq!(sums.smallest, Some(vec![]));
+
+        assert!(subset_sums(&entries(&[0; 128]), 0).is_err(), "2^128 overflows u128");
+    }
+
+    #[test]
+    fn test_checked_product() {
+        assert_eq!(checked_product(&[299, 1721]), Ok(514579));
+        assert_eq!(checked_product(&[]), Ok(1), "empty product");
+        assert_eq!(
+            checked_product(&[i64::MAX, 2]),
+            Err(Error::Overflow { terms: vec![i64::MAX, 2], width: "i64" })
+        );
+        assert_eq!(checked_wide_product(&[i64::MAX, 2]), Ok(i64::MAX as i128 * 2));
+        assert_eq!(
+            checked_wide_product(&[i64::MAX, i64::MAX, 4]).unwrap_err().to_string(),
+            format!("product of {:?} overflows i128", [i64::MAX, i64::MAX, 4])
+        );
+    }
+
+    #[test]
+    fn test_find_k_sum_large_values() {
+        assert_eq!(
+            find_k_sum(&[i64::MAX, 1, -1], 2, i64::MAX - 1),
+            Some(vec![-1, i64::MAX]),
+            "intermediate target doesn't fit in an i64"
+        );
+        assert_eq!(find_k_sum(&[i64::MAX, i64::MAX, 2], 3, 0), None, "sum doesn't wrap around");
+        assert_eq!(
+            lines(find_all_k_sums(&entries(&[i64::MIN, i64::MIN, -1, 1]), 3, i64::MIN)),
+            vec![vec![1, 3, 4], vec![2, 3, 4]],
+            "i64::MIN + i64::MIN is never considered a match"
+        );
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |s: &str| parse_args(s.split_whitespace().map(String::from));
+        assert_eq!(args("").unwrap(), Options { mode: Mode::Tuples, wide: false, path: None });
+        assert_eq!(
+            args("subsets in.txt").unwrap(),
+            Options { mode: Mode::Subsets, wide: false, path: Some("in.txt".into()) }
+        );
+        assert_eq!(
+            args("tuples --wide -").unwrap(),
+            Options { mode: Mode::Tuples, wide: true, path: Some("-".into()) }
+        );
+        assert!(args("--narrow").is_err());
+        assert!(args("a.txt b.txt").is_err(), "only one input");
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        assert_eq!(
+            parse_entries("1721\n  979 \n\n\t366\n").unwrap(),
+            vec![
+                Entry { line: 1, value: 1721 },
+                Entry { line: 2, value: 979 },
+                Entry { line: 4, value: 366 },
+            ],
+            "whitespace and blank lines are skipped, line numbers are kept"
+        );
+        assert_eq!(
+            parse_entries("1721\n\n97x9\n").unwrap_err().to_string(),
+            "line 3: could not parse \"97x9\" as an expense"
+        );
+        assert!(matches!(
+            parse_entries("1\r\n2\r\n99999999999999999999"),
+            Err(Error::Parse { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_streaming_sums() {
+        let mut sums = StreamingSums::new(2020);
+        let found: Vec<_> = entries(EXAMPLE).into_iter().flat_map(|e| sums.push(e)).collect();
+        assert_eq!(
+            found,
+            vec![
+                StreamMatch::Pair([Entry { line: 1, value: 1721 }, Entry { line: 4, value: 299 }]),
+                StreamMatch::Triple([
+                    Entry { line: 2, value: 979 },
+                    Entry { line: 3, value: 366 },
+                    Entry { line: 5, value: 675 },
+                ]),
+            ],
+            "each combination is reported as soon as its last entry is read"
+        );
+        assert!(sums.is_done());
+    }
+
+    #[test]
+    fn test_streaming_sums_no_reuse() {
+        let mut sums = StreamingSums::new(30);
+        let mut found = Vec::new();
+        for e in entries(&[15, 10, 10, 15, 10]) {
+            found.push(sums.push(e));
+        }
+        assert_eq!(found[0], vec![], "15 can't pair with itself");
+        assert_eq!(found[1], vec![], "15 + 10 is not enough");
+        assert_eq!(found[2], vec![], "15 + 10 + 10 is 35");
+        assert_eq!(
+            found[3],
+            vec![StreamMatch::Pair([Entry { line: 1, value: 15 }, Entry { line: 4, value: 15 }])],
+        );
+        assert_eq!(
+            found[4],
+            vec![StreamMatch::Triple([
+                Entry { line: 2, value: 10 },
+                Entry { line: 3, value: 10 },
+                Entry { line: 5, value: 10 },
+            ])],
+            "three equal values on different lines"
+        );
+    }
+
+    #[test]
+    fn test_read_entries() {
+        let read: Vec<_> = read_entries("1721\n\n 979\nabc\n".as_bytes()).collect();
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[0].as_ref().unwrap(), &Entry { line: 1, value: 1721 });
+        assert_eq!(read[1].as_ref().unwrap(), &Entry { line: 3, value: 979 });
+        assert_eq!(
+            read[2].as_ref().unwrap_err().to_string(),
+            "line 4: could not parse \"abc\" as an expense"
+        );
+    }
+}
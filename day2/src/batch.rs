@@ -0,0 +1,156 @@
+// Validation of large inputs: the input is cut into chunks at line boundaries and
+// the chunks are checked in parallel. Lines are borrowed straight from the input,
+// only malformed ones allocate anything.
+
+use rayon::prelude::*;
+
+use super::{ParseError, Rule};
+
+// big enough to amortize the scheduling, small enough to keep every thread busy
+const CHUNK_SIZE: usize = 1 << 20;
+
+// What validating (part of) an input found
+#[derive(Default, PartialEq, Debug)]
+pub(crate) struct Tally {
+    pub(crate) lines: usize,
+    pub(crate) valid: usize,
+    // 1-based line numbers, in input order
+    pub(crate) malformed: Vec<(usize, ParseError)>,
+}
+
+impl Tally {
+    // `other` is what comes right after `self` in the input
+    fn merge(mut self, other: Tally) -> Tally {
+        let offset = self.lines;
+        self.malformed.extend(other.malformed.into_iter().map(|(line, e)| (line + offset, e)));
+        self.lines += other.lines;
+        self.valid += other.valid;
+        self
+    }
+}
+
+pub(crate) fn validate<R: Rule>(
+    input: &[u8],
+    parse: impl Fn(&str) -> Result<(R, &str), ParseError> + Sync,
+) -> Tally {
+    chunks(input, CHUNK_SIZE)
+        .par_iter()
+        .map(|chunk| validate_chunk(chunk, &parse))
+        .reduce(Tally::default, Tally::merge)
+}
+
+fn validate_chunk<R: Rule>(
+    chunk: &[u8],
+    parse: impl Fn(&str) -> Result<(R, &str), ParseError>,
+) -> Tally {
+    let mut tally = Tally::default();
+    for line in lines(chunk) {
+        tally.lines += 1;
+        match to_str(line).and_then(&parse) {
+            Ok((rule, password)) => {
+                if rule.is_valid(password) {
+                    tally.valid += 1;
+                }
+            }
+            Err(e) => tally.malformed.push((tally.lines, e)),
+        }
+    }
+    tally
+}
+
+// splits `input` into chunks of about `size` bytes, each ending with a whole line
+fn chunks(input: &[u8], size: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::with_capacity(input.len() / size.max(1) + 1);
+    let mut rest = input;
+    while !rest.is_empty() {
+        let end = match rest.iter().skip(size).position(|&b| b == b'\n') {
+            Some(i) => size + i + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+// the same lines as `str::lines` would give
+fn lines(chunk: &[u8]) -> impl Iterator<Item = &[u8]> {
+    chunk.split_inclusive(|&b| b == b'\n').map(|line| match line {
+        [line @ .., b'\r', b'\n'] | [line @ .., b'\n'] => line,
+        line => line,
+    })
+}
+
+fn to_str(line: &[u8]) -> Result<&str, ParseError> {
+    std::str::from_utf8(line).map_err(|e| ParseError::InvalidUtf8 {
+        // everything up to the error is valid, so it has a column like any other
+        column: String::from_utf8_lossy(&line[..e.valid_up_to()]).chars().count() + 1,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{chunks, lines, validate, validate_chunk, ParseError, Tally};
+    use crate::{parse_line, Policy, Rule, Semantics};
+
+    fn parse(line: &str) -> Result<(Policy, &str), ParseError> {
+        let (policy, password) = parse_line(line)?;
+        Ok((Policy::new(policy, Semantics::Count)?, password))
+    }
+
+    #[test]
+    fn test_chunks() {
+        let input = b"1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc\n";
+        for size in [0, 1, 5, 13, 14, 100] {
+            let chunks = chunks(input, size);
+            assert_eq!(chunks.concat(), input, "chunks of {} cover the input", size);
+            assert!(chunks.iter().all(|chunk| chunk.ends_with(b"\n")), "whole lines only");
+        }
+        assert_eq!(chunks(input, 14), [&input[..26], &input[26..]]);
+        assert_eq!(chunks(b"ab\ncd", 1), [&b"ab\n"[..], b"cd"], "no newline at the end");
+        assert!(chunks(b"", 1).is_empty());
+    }
+
+    #[test]
+    fn test_lines() {
+        let input = "a\nb\r\n\nc\rd\n\ne";
+        assert_eq!(
+            lines(input.as_bytes()).collect::<Vec<_>>(),
+            input.lines().map(str::as_bytes).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            validate(
+                b"1-3 a: abcde\n1-3 b\n2-9 c: ccccccccc\n1-3 \xff: a\r\n0-1 a: a\n1-x a: a",
+                parse
+            ),
+            Tally {
+                lines: 6,
+                valid: 3,
+                malformed: vec![
+                    (2, ParseError::Expected { expected: "password", column: 6 }),
+                    (4, ParseError::InvalidUtf8 { column: 5 }),
+                    (6, ParseError::Expected { expected: "policy range (upper bound)", column: 3 }),
+                ],
+            }
+        );
+
+        // the same input, cut into many chunks, gives the same tally
+        let input = include_str!("input.txt");
+        let expected = input.lines().filter(|line| {
+            let (policy, password) = parse(line).unwrap();
+            policy.is_valid(password)
+        }).count();
+        let tally = chunks(input.as_bytes(), 100)
+            .into_iter()
+            .map(|chunk| validate_chunk(chunk, parse))
+            .fold(Tally::default(), Tally::merge);
+        assert_eq!(tally, Tally { lines: input.lines().count(), valid: expected, malformed: vec![] });
+        assert_eq!(validate(input.as_bytes(), parse), tally);
+    }
+}
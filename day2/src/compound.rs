@@ -0,0 +1,287 @@
+// Compound policies: several clauses combined with `!`, `&` and `|`, e.g.
+// "1-3 a & 2-9 b | !5 c: password". `!` binds tightest, then `&`, then `|`, and
+// parentheses group as usual. A range clause like "1-3 a" follows the chosen
+// semantics, a single number like "5 c" is one position (or an exact count).
+
+use super::{
+    Bound, CountPolicy, Letter, LinePolicy, ParseError, Policy, PositionPolicy, Rule, Semantics,
+    Verdict, column, to_letter};
+
+#[derive(PartialEq, Debug)]
+pub(crate) enum Expr<L = u8> {
+    Policy(Policy<L>),
+    Not(Box<Expr<L>>),
+    And(Box<Expr<L>>, Box<Expr<L>>),
+    Or(Box<Expr<L>>, Box<Expr<L>>),
+}
+
+// What the grammar produces: numbers and letters along with their offsets, so
+// that they can be checked once we know the letter type
+#[derive(Debug)]
+enum Raw<'a> {
+    Clause {
+        min: (usize, usize),
+        max: Option<(usize, usize)>,
+        letter: (usize, &'a str),
+    },
+    Not(Box<Raw<'a>>),
+    And(Box<Raw<'a>>, Box<Raw<'a>>),
+    Or(Box<Raw<'a>>, Box<Raw<'a>>),
+}
+
+pub(crate) fn parse_compound_line<L: Letter>(
+    s: &str,
+    semantics: Semantics,
+) -> Result<(Expr<L>, &str), ParseError> {
+    peg::parser! {
+        grammar parser() for str {
+            pub(crate) rule line() -> (Raw<'input>, &'input str)
+                = _ e:expr() _ (quiet!{":"} / expected!("password")) " "* password:password() ![_]
+                  { (e, password) }
+
+            rule expr() -> Raw<'input> = precedence! {
+                x:(@) _ quiet!{"|"} _ y:@ { Raw::Or(Box::new(x), Box::new(y)) }
+                --
+                x:(@) _ quiet!{"&"} _ y:@ { Raw::And(Box::new(x), Box::new(y)) }
+                --
+                quiet!{"!"} _ x:@ { Raw::Not(Box::new(x)) }
+                --
+                c:clause() { c }
+                quiet!{"("} _ e:expr() _ (quiet!{")"} / expected!("closing parenthesis")) { e }
+            }
+
+            rule clause() -> Raw<'input>
+                = min:positioned(<number()>)
+                  max:(quiet!{"-"} n:(positioned(<number()>) / expected!("policy range (upper bound)")) { n })?
+                  separator() letter:letter()
+                  { Raw::Clause { min, max, letter } }
+                / expected!("policy clause")
+
+            rule positioned<T>(r: rule<T>) -> (usize, T)
+                = offset:position!() n:r() { (offset, n) }
+
+            rule number() -> usize
+                = n:$(quiet!{['0'..='9']+}) {? n.parse().or(Err("number small enough to fit in a usize")) }
+
+            rule separator() = quiet!{" "+} / expected!("policy byte")
+
+            rule letter() -> (usize, &'input str)
+                = offset:position!() l:$(quiet!{(!([':' | ' ' | '&' | '|' | ')']) [_])*})
+                  { (offset, l) }
+
+            rule password() -> &'input str
+                = p:$([_]*) { p.trim_end() }
+
+            rule _ = quiet!{" "*}
+        }
+    }
+
+    let (raw, password) = parser::line(s).map_err(|e| ParseError::Expected {
+        expected: e.expected.tokens().min().unwrap_or("policy clause"),
+        column: e.location.column,
+    })?;
+    Ok((build(s, raw, semantics)?, password))
+}
+
+fn build<L: Letter>(s: &str, raw: Raw, semantics: Semantics) -> Result<Expr<L>, ParseError> {
+    let boxed = |raw: Box<Raw>| build(s, *raw, semantics).map(Box::new);
+    let bound = |(offset, n)| Bound { n, column: column(s, offset) };
+    Ok(match raw {
+        Raw::Clause { min, max, letter: (offset, letter) } => {
+            let letter = to_letter(s, offset, letter)?;
+            Expr::Policy(match (max, semantics) {
+                // the numbers mean what they do on a policy line of their own
+                (Some(max), _) => {
+                    Policy::new(LinePolicy { letter, bounds: [bound(min), bound(max)] }, semantics)?
+                }
+                (None, Semantics::Count) => Policy::Count(CountPolicy {
+                    letter,
+                    range: min.1..=min.1,
+                }),
+                (None, Semantics::Positions) => Policy::Position(PositionPolicy {
+                    letter,
+                    position: bound(min).to_position()?,
+                }),
+            })
+        }
+        Raw::Not(x) => Expr::Not(boxed(x)?),
+        Raw::And(x, y) => Expr::And(boxed(x)?, boxed(y)?),
+        Raw::Or(x, y) => Expr::Or(boxed(x)?, boxed(y)?),
+    })
+}
+
+impl<L: Letter> Rule for Expr<L> {
+    fn is_valid(&self, password: &str) -> bool {
+        match self {
+            Expr::Policy(policy) => policy.is_valid(password),
+            Expr::Not(x) => !x.is_valid(password),
+            Expr::And(x, y) => x.is_valid(password) && y.is_valid(password),
+            Expr::Or(x, y) => x.is_valid(password) || y.is_valid(password),
+        }
+    }
+
+    // every clause is explained, even those that evaluation would short-circuit
+    fn explain(&self, password: &str) -> Verdict {
+        match self {
+            Expr::Policy(policy) => policy.explain(password),
+            Expr::Not(x) => {
+                let x = x.explain(password);
+                Verdict {
+                    valid: !x.valid,
+                    reason: format!("not ({})", x.reason),
+                }
+            }
+            Expr::And(x, y) => {
+                let (x, y) = (x.explain(password), y.explain(password));
+                Verdict {
+                    valid: x.valid && y.valid,
+                    reason: format!("({}) and ({})", x.reason, y.reason),
+                }
+            }
+            Expr::Or(x, y) => {
+                let (x, y) = (x.explain(password), y.explain(password));
+                Verdict {
+                    valid: x.valid || y.valid,
+                    reason: format!("({}) or ({})", x.reason, y.reason),
+                }
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Expr::Policy(policy) => policy.describe(),
+            Expr::Not(x) => format!("not ({})", x.describe()),
+            Expr::And(x, y) => format!("({}) and ({})", x.describe(), y.describe()),
+            Expr::Or(x, y) => format!("({}) or ({})", x.describe(), y.describe()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CountPolicy, Expr, ParseError, Policy, PositionPolicy, Rule, Semantics,
+        parse_compound_line};
+    use crate::PasswordPolicy;
+
+    fn count(letter: u8, min: usize, max: usize) -> Box<Expr> {
+        Box::new(Expr::Policy(Policy::Count(CountPolicy { letter, range: min..=max })))
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let (expr, password) =
+            parse_compound_line::<u8>("1-3 a & 2-9 b | !5 c: password", Semantics::Count).unwrap();
+        assert_eq!(password, "password");
+        assert_eq!(
+            expr,
+            Expr::Or(
+                Box::new(Expr::And(count(b'a', 1, 3), count(b'b', 2, 9))),
+                Box::new(Expr::Not(count(b'c', 5, 5))),
+            ),
+            "'&' binds tighter than '|', '!' tighter than both"
+        );
+
+        let (expr, _) =
+            parse_compound_line::<u8>("!(1-3 a|2 b)&3 c: x", Semantics::Positions).unwrap();
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::Not(Box::new(Expr::Or(
+                    Box::new(Expr::Policy(Policy::Positions(PasswordPolicy {
+                        letter: b'a',
+                        positions: [0, 2],
+                    }))),
+                    Box::new(Expr::Policy(Policy::Position(PositionPolicy {
+                        letter: b'b',
+                        position: 1,
+                    }))),
+                )))),
+                Box::new(Expr::Policy(Policy::Position(PositionPolicy {
+                    letter: b'c',
+                    position: 2,
+                }))),
+            ),
+            "parentheses group, single numbers are single positions"
+        );
+
+        let (expr, _) = parse_compound_line::<u8>("1-3 a: abcde", Semantics::Positions).unwrap();
+        assert_eq!(
+            expr,
+            Expr::Policy(Policy::Positions(PasswordPolicy { letter: b'a', positions: [0, 2] })),
+            "a plain policy line is a compound of one clause"
+        );
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let valid = |s, semantics| {
+            let (expr, password) = parse_compound_line::<u8>(s, semantics).unwrap();
+            assert_eq!(expr.explain(password).valid, expr.is_valid(password), "{}", s);
+            expr.is_valid(password)
+        };
+        assert!(valid("1-3 a & 2-9 b | !5 c: aabb", Semantics::Count));
+        assert!(valid("1-3 a & 2-9 b | !5 c: a", Semantics::Count), "no five 'c's");
+        assert!(!valid("1-3 a & 2-9 b | !5 c: accccc", Semantics::Count));
+        assert!(valid("1-3 a & (2-9 b | !5 c): accc", Semantics::Count), "not five 'c's");
+        assert!(valid("1 a & !2 a: ab", Semantics::Positions));
+        assert!(!valid("1 a & !2 a: aa", Semantics::Positions));
+        assert!(!valid("!!1 a: ba", Semantics::Positions));
+        assert!(valid("0 a: bcd", Semantics::Count), "exactly zero 'a's");
+        assert!(valid("0-2 a & 1 b: abc", Semantics::Count), "zero to two 'a's");
+        assert!(valid("0-2 a & 1 b: bcd", Semantics::Count));
+        assert!(!valid("0-2 a & 1 b: aaab", Semantics::Count));
+    }
+
+    #[test]
+    fn test_explain() {
+        let (expr, password) =
+            parse_compound_line::<u8>("1-2 a & !3 b: aab", Semantics::Positions).unwrap();
+        assert_eq!(
+            expr.describe(),
+            "('a' at exactly one of positions 1, 2) and (not ('b' at position 3))"
+        );
+        let verdict = expr.explain(password);
+        assert!(!verdict.valid);
+        assert_eq!(
+            verdict.reason,
+            "(position 1 has 'a', position 2 has 'a', both match) and (not (position 3 has 'b'))"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |s| parse_compound_line::<u8>(s, Semantics::Positions).unwrap_err();
+        assert_eq!(err("1-3 a &"), ParseError::Expected { expected: "policy clause", column: 8 });
+        assert_eq!(err("1-3 a & 2 b"), ParseError::Expected { expected: "password", column: 12 });
+        assert_eq!(
+            err("(1-3 a & 2 b: x"),
+            ParseError::Expected { expected: "closing parenthesis", column: 13 }
+        );
+        assert_eq!(
+            err("1- a: x"),
+            ParseError::Expected { expected: "policy range (upper bound)", column: 3 }
+        );
+        assert_eq!(err("1 é & 0 b: x"), ParseError::Expected {
+            expected: "password policy byte to be exactly 1 byte",
+            column: 3,
+        });
+        assert_eq!(
+            parse_compound_line::<char>("1 é & 0 b: x", Semantics::Positions).unwrap_err(),
+            ParseError::ZeroPosition { column: 7 },
+            "columns after a multi-byte letter"
+        );
+        assert_eq!(
+            err("1 a & 0-2 b: x"),
+            ParseError::ZeroPosition { column: 7 },
+            "a range of positions"
+        );
+        assert_eq!(
+            parse_compound_line::<u8>("0-2 a & 1 b: x", Semantics::Count).unwrap().0,
+            Expr::And(count(b'a', 0, 2), count(b'b', 1, 1)),
+            "a range of counts from zero"
+        );
+    }
+}
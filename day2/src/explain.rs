@@ -0,0 +1,142 @@
+// Output formats for the per-line explanations of `--explain`
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Format {
+    Text,
+    // one JSON object per line
+    Json,
+    Csv,
+}
+
+// Everything `--explain` reports about one input line
+#[derive(PartialEq, Debug)]
+pub(crate) struct Record<'a> {
+    pub(crate) line: usize,
+    // empty for malformed lines, like `password`
+    pub(crate) policy: String,
+    pub(crate) password: &'a str,
+    pub(crate) verdict: &'static str,
+    pub(crate) reason: String,
+}
+
+impl Format {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn header(self) -> Option<&'static str> {
+        match self {
+            Format::Csv => Some("line,policy,password,verdict,reason"),
+            Format::Text | Format::Json => None,
+        }
+    }
+
+    pub(crate) fn format(self, r: &Record) -> String {
+        match self {
+            Format::Text if r.policy.is_empty() => {
+                format!("line {}: {} ({})", r.line, r.verdict, r.reason)
+            }
+            Format::Text => format!(
+                "line {}: {}, password {:?}: {} ({})",
+                r.line, r.policy, r.password, r.verdict, r.reason
+            ),
+            Format::Json => format!(
+                r#"{{"line":{},"policy":{},"password":{},"verdict":{},"reason":{}}}"#,
+                r.line,
+                json_string(&r.policy),
+                json_string(r.password),
+                json_string(r.verdict),
+                json_string(&r.reason),
+            ),
+            Format::Csv => format!(
+                "{},{},{},{},{}",
+                r.line,
+                csv_field(&r.policy),
+                csv_field(r.password),
+                csv_field(r.verdict),
+                csv_field(&r.reason),
+            ),
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+// quotes a field only if it has to be, doubling any quotes inside (RFC 4180)
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Record};
+
+    #[test]
+    fn test_format() {
+        let r = Record {
+            line: 1,
+            policy: "'j' at exactly one of positions 3, 4".into(),
+            password: "tj\"j,j",
+            verdict: "invalid",
+            reason: "position 3 has '\"', position 4 has 'j', exactly one match".into(),
+        };
+        assert_eq!(
+            Format::Text.format(&r),
+            "line 1: 'j' at exactly one of positions 3, 4, password \"tj\\\"j,j\": invalid \
+             (position 3 has '\"', position 4 has 'j', exactly one match)"
+        );
+        assert_eq!(
+            Format::Json.format(&r),
+            r#"{"line":1,"policy":"'j' at exactly one of positions 3, 4","password":"tj\"j,j","#
+                .to_string()
+                + r#""verdict":"invalid","reason":"position 3 has '\"', position 4 has 'j', exactly one match"}"#
+        );
+        assert_eq!(
+            Format::Csv.format(&r),
+            r#"1,"'j' at exactly one of positions 3, 4","tj""j,j",invalid,"position 3 has '""', position 4 has 'j', exactly one match""#
+        );
+    }
+
+    #[test]
+    fn test_format_malformed() {
+        let r = Record {
+            line: 7,
+            policy: String::new(),
+            password: "",
+            verdict: "malformed",
+            reason: "column 6: expected password".into(),
+        };
+        assert_eq!(Format::Text.format(&r), "line 7: malformed (column 6: expected password)");
+        assert_eq!(Format::Csv.format(&r), "7,,,malformed,column 6: expected password");
+        assert_eq!(
+            Format::Json.format(&Record { reason: "a\tb\u{1}".into(), ..r }),
+            r#"{"line":7,"policy":"","password":"","verdict":"malformed","reason":"a\tb\u0001"}"#
+        );
+    }
+}
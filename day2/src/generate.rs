@@ -0,0 +1,387 @@
+// Random passwords that are known to satisfy (or violate) a policy, and whole
+// synthetic input files made of them, for stress-testing whatever consumes them.
+
+use std::{io, ops::RangeInclusive};
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{Letter, LinePolicy, Policy, PolicyLine, Rule, Semantics};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub(crate) enum GenerateError {
+    #[error("the alphabet is empty")]
+    EmptyAlphabet,
+
+    #[error("{0} can't be written as a single letter of a policy line")]
+    Unwritable(String),
+
+    #[error("the password length range is empty")]
+    EmptyLength,
+
+    #[error("no password of the allowed lengths can be {verdict} under \"{policy}\"")]
+    Impossible { verdict: &'static str, policy: String },
+}
+
+/// Generates passwords from `alphabet`, with a length in `length`. The same seed
+/// always produces the same passwords.
+pub(crate) struct Generator<L> {
+    alphabet: Vec<L>,
+    length: RangeInclusive<usize>,
+    rng: ChaCha8Rng,
+}
+
+impl<L: Letter> Generator<L> {
+    pub(crate) fn new(
+        seed: u64,
+        letters: impl IntoIterator<Item = L>,
+        length: RangeInclusive<usize>,
+    ) -> Result<Self, GenerateError> {
+        // duplicates would skew the distribution towards them
+        let mut alphabet = Vec::new();
+        for l in letters {
+            if !alphabet.contains(&l) {
+                alphabet.push(l);
+            }
+        }
+        if alphabet.is_empty() {
+            return Err(GenerateError::EmptyAlphabet);
+        }
+        // passwords end up in policy lines, so every letter has to read back as itself
+        // and can't be mistaken for a separator
+        if let Some(l) = alphabet.iter().find(|&&l| {
+            let c = l.to_char();
+            c.is_whitespace() || c == ':' || L::from_str(&c.to_string()) != Some(l)
+        }) {
+            return Err(GenerateError::Unwritable(l.describe()));
+        }
+        if length.is_empty() {
+            return Err(GenerateError::EmptyLength);
+        }
+        Ok(Self {
+            alphabet,
+            length,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        })
+    }
+
+    /// A random password that `policy` accepts if `valid`, or rejects otherwise.
+    pub(crate) fn password(&mut self, policy: &Policy<L>, valid: bool) -> Result<String, GenerateError> {
+        // some lengths can make a verdict impossible (a position past the end can't
+        // match), so try a length from every stretch of them, in random order
+        let mut stretches = self.stretches(policy);
+        stretches.shuffle(&mut self.rng);
+        for stretch in stretches {
+            let len = self.rng.gen_range(stretch);
+            if let Some(letters) = self.letters(policy, valid, len) {
+                let password = letters.into_iter().map(L::to_char).collect::<String>();
+                debug_assert_eq!(policy.is_valid(&password), valid);
+                return Ok(password);
+            }
+        }
+        Err(GenerateError::Impossible {
+            verdict: if valid { "valid" } else { "invalid" },
+            policy: policy.describe(),
+        })
+    }
+
+    // The password lengths, cut wherever a longer password could get another verdict:
+    // every length in a stretch is as good as any other.
+    fn stretches(&self, policy: &Policy<L>) -> Vec<RangeInclusive<usize>> {
+        let (start, end) = (*self.length.start(), *self.length.end());
+        // the empty password has no positions at all
+        let mut cuts = vec![start, 1];
+        match policy {
+            Policy::Count(policy) => {
+                cuts.extend([*policy.range.start(), policy.range.end().saturating_add(1)])
+            }
+            Policy::Positions(policy) => cuts.extend(policy.positions.map(|p| p.saturating_add(1))),
+            Policy::Position(policy) => cuts.push(policy.position.saturating_add(1)),
+        }
+        cuts.retain(|&cut| (start..=end).contains(&cut));
+        cuts.sort_unstable();
+        cuts.dedup();
+        // `start` is always the first cut
+        let ends = cuts.iter().skip(1).map(|&cut| cut - 1).chain([end]);
+        cuts.iter().zip(ends).map(|(&from, to)| from..=to).collect()
+    }
+
+    /// A random policy line with numbers within the password lengths and a letter
+    /// from the alphabet
+    pub(crate) fn policy(&mut self) -> LinePolicy<L> {
+        let max = (*self.length.end()).max(1);
+        let mut bounds = [self.rng.gen_range(1..=max), self.rng.gen_range(1..=max)];
+        bounds.sort_unstable();
+        LinePolicy::new(*self.alphabet.choose(&mut self.rng).unwrap(), bounds)
+    }
+
+    // `len` letters satisfying the verdict, or None if there are none
+    fn letters(&mut self, policy: &Policy<L>, valid: bool, len: usize) -> Option<Vec<L>> {
+        match policy {
+            Policy::Count(policy) => {
+                let (start, end) = (*policy.range.start(), *policy.range.end());
+                let counts = if valid {
+                    vec![start..=end]
+                } else {
+                    let below = (start > 0).then(|| 0..=start - 1);
+                    let above = (end < len).then(|| end + 1..=len);
+                    below.into_iter().chain(above).collect()
+                };
+                // without the letter there can't be any, and with nothing else it has
+                // to be everywhere
+                let counts: Vec<RangeInclusive<usize>> = counts
+                    .into_iter()
+                    .map(|counts| {
+                        let (mut min, mut max) = (*counts.start(), (*counts.end()).min(len));
+                        if !self.has_others(policy.letter) {
+                            min = min.max(len);
+                        }
+                        if !self.alphabet.contains(&policy.letter) {
+                            max = 0;
+                        }
+                        min..=max
+                    })
+                    .filter(|counts| !counts.is_empty())
+                    .collect();
+                // every count as likely as any other
+                let total: usize = counts.iter().map(|c| c.end() - c.start() + 1).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut k = self.rng.gen_range(0..total);
+                let n = counts.iter().find_map(|c| match c.end() - c.start() + 1 {
+                    size if k < size => Some(c.start() + k),
+                    size => {
+                        k -= size;
+                        None
+                    }
+                })?;
+                let mut letters = self.others(policy.letter, len - n);
+                letters.extend(std::iter::repeat_n(policy.letter, n));
+                letters.shuffle(&mut self.rng);
+                Some(letters)
+            }
+            Policy::Positions(policy) => {
+                // which of the two positions should have the letter
+                let [p, q] = policy.positions;
+                let options: Vec<[bool; 2]> = [[true, false], [false, true], [true, true], [false, false]]
+                    .into_iter()
+                    .filter(|&[at_p, at_q]| {
+                        // the same position can't both have the letter and not
+                        p != q || at_p == at_q
+                    })
+                    .filter(|&[at_p, at_q]| {
+                        // like `is_valid`, a repeated position counts twice
+                        let matches = (at_p && p < len) as usize + (at_q && q < len) as usize;
+                        (matches == 1) == valid
+                    })
+                    .filter(|&[at_p, at_q]| {
+                        self.can_set(policy.letter, len, &[(p, at_p), (q, at_q)])
+                    })
+                    .collect();
+                let &[at_p, at_q] = options.choose(&mut self.rng)?;
+                Some(self.with_positions(policy.letter, len, &[(p, at_p), (q, at_q)]))
+            }
+            Policy::Position(policy) => {
+                let p = policy.position;
+                let options: Vec<bool> = [true, false]
+                    .into_iter()
+                    .filter(|&at_p| (at_p && p < len) == valid)
+                    .filter(|&at_p| self.can_set(policy.letter, len, &[(p, at_p)]))
+                    .collect();
+                let &at_p = options.choose(&mut self.rng)?;
+                Some(self.with_positions(policy.letter, len, &[(p, at_p)]))
+            }
+        }
+    }
+
+    // random letters, except that every position within `len` is (or isn't) `letter`,
+    // as long as `can_set` says it can be
+    fn with_positions(&mut self, letter: L, len: usize, positions: &[(usize, bool)]) -> Vec<L> {
+        let mut letters: Vec<L> = (0..len)
+            .map(|_| *self.alphabet.choose(&mut self.rng).unwrap())
+            .collect();
+        for &(p, at_p) in positions.iter().filter(|(p, _)| *p < len) {
+            letters[p] = if at_p { letter } else { self.others(letter, 1)[0] };
+        }
+        letters
+    }
+
+    // whether the alphabet has what it takes for `with_positions`
+    fn can_set(&self, letter: L, len: usize, positions: &[(usize, bool)]) -> bool {
+        positions.iter().filter(|(p, _)| *p < len).all(|&(_, at_p)| {
+            if at_p {
+                self.alphabet.contains(&letter)
+            } else {
+                self.has_others(letter)
+            }
+        })
+    }
+
+    fn has_others(&self, letter: L) -> bool {
+        self.alphabet.iter().any(|&l| l != letter)
+    }
+
+    // `n` random letters that are all different from `letter`
+    fn others(&mut self, letter: L, n: usize) -> Vec<L> {
+        let others: Vec<L> = self.alphabet.iter().copied().filter(|&l| l != letter).collect();
+        (0..n).filter_map(|_| others.choose(&mut self.rng).copied()).collect()
+    }
+
+    /// Writes `lines` random policy lines, of which `ratio` (rounded to the nearest
+    /// line) are valid under `semantics`.
+    pub(crate) fn write_input(
+        &mut self,
+        mut w: impl io::Write,
+        lines: usize,
+        ratio: f64,
+        semantics: Semantics,
+    ) -> anyhow::Result<()> {
+        let num_valid = ((lines as f64) * ratio.clamp(0.0, 1.0)).round() as usize;
+        let mut verdicts: Vec<bool> = (0..lines).map(|i| i < num_valid).collect();
+        verdicts.shuffle(&mut self.rng);
+
+        for valid in verdicts {
+            // not every policy can go both ways, just draw another one
+            let line = (0..1000)
+                .find_map(|_| {
+                    let policy = self.policy();
+                    // from 1, the numbers stand for positions as well as counts
+                    let rule = Policy::new(policy.clone(), semantics).ok()?;
+                    let password = self.password(&rule, valid).ok()?;
+                    Some(PolicyLine { policy, password })
+                })
+                .ok_or_else(|| anyhow::anyhow!(
+                    "could not find a policy with {} passwords, is the alphabet too small?",
+                    if valid { "valid" } else { "invalid" }
+                ))?;
+            writeln!(w, "{}", line)?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{GenerateError, Generator};
+    use crate::{parse_line_in, Letter, Policy, PolicyLine, Rule, Semantics};
+
+    fn policy<L: Letter>(s: &str, semantics: Semantics) -> Policy<L> {
+        Policy::new(parse_line_in(s).unwrap().0, semantics).unwrap()
+    }
+
+    #[test]
+    fn test_password() {
+        let mut gen = Generator::new(42, b"abc".iter().copied(), 0..=8).unwrap();
+        for s in ["1-3 a", "2-4 b", "5-8 c", "1-2 a", "3-5 a"] {
+            for semantics in [Semantics::Count, Semantics::Positions] {
+                let policy = policy::<u8>(&format!("{}: x", s), semantics);
+                for valid in [true, false] {
+                    for _ in 0..50 {
+                        let password = gen.password(&policy, valid).unwrap();
+                        assert!((0..=8).contains(&password.len()), "{:?}", password);
+                        assert!(password.bytes().all(|b| b"abc".contains(&b)), "{:?}", password);
+                        assert_eq!(policy.is_valid(&password), valid, "{} {:?}", s, password);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_password_chars() {
+        let mut gen = Generator::new(7, "éàü".chars(), 3..=3).unwrap();
+        let policy = policy::<char>("1-3 é: x", Semantics::Positions);
+        let password = gen.password(&policy, true).unwrap();
+        assert_eq!(password.chars().count(), 3);
+        assert!(policy.is_valid(&password));
+    }
+
+    #[test]
+    fn test_stretches() {
+        let gen = Generator::new(0, b"ab".iter().copied(), 0..=4_000_000_000).unwrap();
+        assert_eq!(
+            gen.stretches(&policy::<u8>("3-7 a: x", Semantics::Positions)),
+            [0..=0, 1..=2, 3..=6, 7..=4_000_000_000],
+            "no lengths to go through one by one"
+        );
+        assert_eq!(
+            gen.stretches(&policy::<u8>("2-5 a: x", Semantics::Count)),
+            [0..=0, 1..=1, 2..=5, 6..=4_000_000_000]
+        );
+        let gen = Generator::new(0, b"ab".iter().copied(), 4..=6).unwrap();
+        assert_eq!(gen.stretches(&policy::<u8>("3-5 a: x", Semantics::Positions)), [4..=4, 5..=6]);
+        assert_eq!(gen.stretches(&policy::<u8>("9-9 a: x", Semantics::Count)), [4..=6]);
+    }
+
+    #[test]
+    fn test_seed() {
+        let passwords = |seed| {
+            let mut gen = Generator::new(seed, b'a'..=b'z', 5..=20).unwrap();
+            let policy = policy::<u8>("2-9 c: x", Semantics::Count);
+            (0..10).map(|_| gen.password(&policy, true).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(passwords(1), passwords(1), "same seed, same passwords");
+        assert_ne!(passwords(1), passwords(2));
+    }
+
+    #[test]
+    fn test_impossible() {
+        let mut gen = Generator::new(0, b"a".iter().copied(), 2..=5).unwrap();
+        assert_eq!(
+            gen.password(&policy::<u8>("1-2 a: x", Semantics::Positions), true),
+            Err(GenerateError::Impossible {
+                verdict: "valid",
+                policy: "'a' at exactly one of positions 1, 2".into(),
+            }),
+            "with only 'a's, a password of length 2 or more has 'a' at both positions"
+        );
+        let password = gen.password(&policy::<u8>("1-2 a: x", Semantics::Positions), false).unwrap();
+        assert!(password.len() >= 2, "{:?}", password);
+        assert!(gen.password(&policy::<u8>("9-9 a: x", Semantics::Count), true).is_err());
+        let mut gen = Generator::new(0, b'a'..=b'z', 1..=5).unwrap();
+        assert!(
+            gen.password(&policy::<u8>("2-2 b: x", Semantics::Positions), true).is_err(),
+            "a repeated position matches twice or not at all"
+        );
+
+        assert_eq!(Generator::<u8>::new(0, [], 1..=5).err(), Some(GenerateError::EmptyAlphabet));
+        assert_eq!(
+            Generator::new(0, [b'a', 0xc3], 1..=5).err(),
+            Some(GenerateError::Unwritable("byte 0xc3".into()))
+        );
+        assert_eq!(
+            Generator::new(0, "a:".chars(), 1..=5).err(),
+            Some(GenerateError::Unwritable("':'".into()))
+        );
+        let mut gen = Generator::new(0, b"ab".iter().copied(), 3..=3).unwrap();
+        let missing = policy::<u8>("1-2 c: x", Semantics::Positions);
+        for _ in 0..100 {
+            assert_eq!(gen.password(&missing, false).unwrap().len(), 3, "'c' just can't be anywhere");
+            assert!(gen.password(&missing, true).is_err());
+        }
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 5..=1;
+        assert_eq!(Generator::new(0, [b'a'], empty).err(), Some(GenerateError::EmptyLength));
+    }
+
+    #[test]
+    fn test_write_input() {
+        let mut gen = Generator::new(3, b'a'..=b'e', 1..=12).unwrap();
+        let mut out = Vec::new();
+        gen.write_input(&mut out, 200, 0.25, Semantics::Count).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 200);
+        let valid = out.lines()
+            .map(|s| s.parse::<PolicyLine>().unwrap())
+            .filter(|line| {
+                Policy::new(line.policy.clone(), Semantics::Count).unwrap().is_valid(&line.password)
+            })
+            .count();
+        assert_eq!(valid, 50, "exactly a quarter of the lines are valid");
+    }
+}
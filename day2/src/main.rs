@@ -1,114 +1,657 @@
-use std::ops::RangeInclusive;
+use std::{fmt, fs::File, io, ops::RangeInclusive, path::PathBuf, str::FromStr};
 
-#[derive(PartialEq, Debug)]
-struct PasswordPolicy {
-    byte: u8,
+use anyhow::Context;
+use compound::parse_compound_line;
+use explain::{Format, Record};
+use generate::Generator;
+use memmap2::Mmap;
+
+mod batch;
+mod compound;
+mod explain;
+mod generate;
+
+// `letter` is a byte by default, or a `char` for Unicode-aware policies
+#[derive(Clone, PartialEq, Debug)]
+struct PasswordPolicy<L = u8> {
+    letter: L,
     positions: [usize; 2],
 }
 
+// A policy as its line writes it, before `Policy::new` gives its numbers a meaning
+#[derive(Clone, PartialEq, Debug)]
+struct LinePolicy<L = u8> {
+    letter: L,
+    bounds: [Bound; 2],
+}
+
+// A number of a policy line as written, and the column it starts at
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Bound {
+    n: usize,
+    column: usize,
+}
+
+// The part 1 reading of a policy line: `letter` has to occur `range` times
+#[derive(PartialEq, Debug)]
+struct CountPolicy<L = u8> {
+    letter: L,
+    range: RangeInclusive<usize>,
+}
+
+/// The unit policies count in. Bytes are what the puzzle uses, characters make
+/// non-ASCII passwords work: both the policy letter and the positions are then
+/// counted in `char`s.
+trait Letter: Copy + PartialEq + fmt::Debug {
+    // what a policy letter that isn't exactly one unit long is reported as
+    const EXPECTED: &'static str;
+
+    fn from_str(s: &str) -> Option<Self>;
+
+    fn letters(password: &str) -> impl Iterator<Item = Self> + '_;
+
+    // how the letter is quoted in explanations
+    fn describe(self) -> String;
+
+    // how the letter is written in policy lines; for bytes only ASCII reads back
+    fn to_char(self) -> char;
+}
+
+impl Letter for u8 {
+    const EXPECTED: &'static str = "password policy byte to be exactly 1 byte";
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s.as_bytes() {
+            &[b] => Some(b),
+            _ => None,
+        }
+    }
+
+    fn letters(password: &str) -> impl Iterator<Item = Self> + '_ {
+        password.bytes()
+    }
+
+    fn describe(self) -> String {
+        if self.is_ascii() {
+            format!("{:?}", self as char)
+        } else {
+            format!("byte 0x{:02x}", self)
+        }
+    }
+
+    fn to_char(self) -> char {
+        self as char
+    }
+}
+
+impl Letter for char {
+    const EXPECTED: &'static str = "password policy character to be exactly 1 character";
+
+    fn from_str(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    fn letters(password: &str) -> impl Iterator<Item = Self> + '_ {
+        password.chars()
+    }
+
+    fn describe(self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn to_char(self) -> char {
+        self
+    }
+}
+
+// Which of the two rules the numbers of a policy line stand for
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Semantics {
+    // part 1: "1-3 a" means 1 to 3 occurrences of 'a'
+    Count,
+    // part 2: "1-3 a" means 'a' at exactly one of the positions 1 and 3
+    Positions,
+}
+
+/// A whole line of input. Unlike the result of `parse_line`, it owns its password,
+/// so that it can implement `FromStr`; `Display` writes it back in the input format.
+#[derive(PartialEq, Debug)]
+struct PolicyLine<L = u8> {
+    policy: LinePolicy<L>,
+    password: String,
+}
+
+// A single letter at a single position, from a clause like "5 c" in a compound policy
+#[derive(PartialEq, Debug)]
+struct PositionPolicy<L = u8> {
+    letter: L,
+    position: usize,
+}
+
+// A policy line, interpreted with either semantics
+#[derive(PartialEq, Debug)]
+enum Policy<L = u8> {
+    Count(CountPolicy<L>),
+    Positions(PasswordPolicy<L>),
+    Position(PositionPolicy<L>),
+}
+
+// Whether a password satisfies its policy, and why
+#[derive(PartialEq, Debug)]
+struct Verdict {
+    valid: bool,
+    reason: String,
+}
+
+/// Anything a password can be checked against: a single policy, or a compound
+/// expression of several.
+trait Rule {
+    fn is_valid(&self, password: &str) -> bool;
+
+    fn explain(&self, password: &str) -> Verdict;
+
+    // a human-readable summary of the rule, for explanations
+    fn describe(&self) -> String;
+}
+
 fn main() -> anyhow::Result<()> {
-    let count = include_str!("input.txt")
-        .lines().map(parse_line)
-        .map(Result::unwrap)
-        .filter(|(policy, password)| policy.is_valid(password))
-        .count();
-    println!("{} passwords are valid", count);
+    let (mut semantics, mut chars, mut compound) = (Semantics::Positions, false, false);
+    let (mut explain, mut normalize) = (None, false);
+    // `--generate` writes a synthetic input to stdout instead of checking one
+    let (mut generate, mut ratio, mut seed) = (None, 0.5, 0);
+    let (mut length, mut alphabet) = (1..=20, String::from("abcdefghijklmnopqrstuvwxyz"));
+    // the puzzle input, unless a file is given
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--positions" => semantics = Semantics::Positions,
+            "--count" => semantics = Semantics::Count,
+            "--bytes" => chars = false,
+            "--chars" => chars = true,
+            "--compound" => compound = true,
+            "--normalize" => normalize = true,
+            "--explain" => explain = Some(Format::Text),
+            other => match other.split_once('=') {
+                Some(("--explain", format)) => {
+                    explain = Some(Format::parse(format).with_context(|| {
+                        format!("unknown format in {:?}, expected 'text', 'json' or 'csv'", other)
+                    })?)
+                }
+                Some(("--generate", lines)) => generate = Some(parse_value(other, lines)?),
+                Some(("--ratio", r)) => ratio = parse_value(other, r)?,
+                Some(("--seed", s)) => seed = parse_value(other, s)?,
+                Some(("--length", range)) => {
+                    let (min, max) = range.split_once('-').unwrap_or((range, range));
+                    length = parse_value(other, min)?..=parse_value(other, max)?;
+                }
+                Some(("--alphabet", letters)) => alphabet = letters.to_string(),
+                _ if !other.starts_with("--") => path = Some(PathBuf::from(other)),
+                _ => anyhow::bail!(
+                    "unknown argument {:?}, expected '--count', '--positions', '--bytes', \
+                     '--chars', '--compound', '--normalize', '--explain[=text|json|csv]' or \
+                     '--generate=LINES' with '--ratio=R', '--seed=S', '--length=MIN-MAX' \
+                     and '--alphabet=LETTERS', or a path to the input",
+                    other
+                ),
+            },
+        }
+    }
+
+    if let Some(lines) = generate {
+        let out = io::BufWriter::new(io::stdout().lock());
+        if chars {
+            Generator::new(seed, alphabet.chars(), length)?
+                .write_input(out, lines, ratio, semantics)?;
+        } else {
+            Generator::new(seed, alphabet.bytes(), length)?
+                .write_input(out, lines, ratio, semantics)?;
+        }
+        return Ok(());
+    }
+
+    let mmap;
+    let input: &[u8] = match path {
+        Some(path) => {
+            let file = File::open(&path)
+                .with_context(|| format!("could not open {}", path.display()))?;
+            // SAFETY: the file must not change while it's mapped, like any input that
+            // is being read
+            mmap = unsafe { Mmap::map(&file) }
+                .with_context(|| format!("could not map {}", path.display()))?;
+            &mmap
+        }
+        None => include_str!("input.txt").as_bytes(),
+    };
+    if normalize {
+        let input = to_str(input)?;
+        if chars {
+            normalize_lines::<char>(input);
+        } else {
+            normalize_lines::<u8>(input);
+        }
+        return Ok(());
+    }
+
+    match (chars, compound) {
+        (false, false) => run(input, explain, |line| {
+            let (policy, password) = parse_line(line)?;
+            Ok((Policy::new(policy, semantics)?, password))
+        }),
+        (true, false) => run(input, explain, |line| {
+            let (policy, password) = parse_line_in::<char>(line)?;
+            Ok((Policy::new(policy, semantics)?, password))
+        }),
+        (false, true) => run(input, explain, |line| parse_compound_line::<u8>(line, semantics)),
+        (true, true) => run(input, explain, |line| parse_compound_line::<char>(line, semantics)),
+    }
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: &str) -> anyhow::Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value.parse().with_context(|| format!("invalid value in {:?}", arg))
+}
 
+fn run<R: Rule>(
+    input: &[u8],
+    explain: Option<Format>,
+    parse: impl Fn(&str) -> Result<(R, &str), ParseError> + Sync,
+) -> anyhow::Result<()> {
+    match explain {
+        Some(format) => explain_lines(to_str(input)?, parse, format),
+        None => count_valid(input, parse),
+    }
     Ok(())
 }
 
-fn parse_line(s: &str) -> anyhow::Result<(PasswordPolicy, &str)> {
-    let (policy, password) = {
-        let mut tokens = s.split(':');
-        (
-            tokens.next()
-                .ok_or(ParseError::Expected("password policy"))?,
-            tokens.next()
-                .ok_or(ParseError::Expected("password"))?
-                .trim(),
-        )
-    };
+// the whole input as text, for the modes that go through it line by line
+fn to_str(input: &[u8]) -> anyhow::Result<&str> {
+    std::str::from_utf8(input).context("the input is not valid UTF-8")
+}
 
-    let (range, byte) = {
-        let mut tokens = policy.split(' ');
-        (
-            tokens.next()
-                .ok_or(ParseError::Expected("policy range"))?,
-            tokens.next()
-                .ok_or(ParseError::Expected("policy byte"))?,
-        )
-    };
+fn count_valid<R: Rule>(input: &[u8], parse: impl Fn(&str) -> Result<(R, &str), ParseError> + Sync) {
+    // a malformed line is reported and skipped, the rest of the batch still counts
+    let tally = batch::validate(input, parse);
+    for (line, e) in &tally.malformed {
+        eprintln!("line {}, column {}: {}", line, e.column(), e);
+    }
+    println!("{} passwords are valid", tally.valid);
+    if !tally.malformed.is_empty() {
+        println!("{} lines could not be parsed", tally.malformed.len());
+    }
+}
 
-    let byte = if byte.as_bytes().len() == 1 {
-        byte.as_bytes()[0]
-    } else {
-        return Err(ParseError::Expected("password policy byte to be exactly 1 byte").into());
-    };
+// writes every line back out in the canonical input format
+fn normalize_lines<L: Letter>(input: &str) {
+    for (i, line) in input.lines().enumerate() {
+        match line.parse::<PolicyLine<L>>() {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("line {}, column {}: {}", i + 1, e.column(), e),
+        }
+    }
+}
 
-    let (min, max) = {
-        let mut tokens = range.split('-');
-        (
-            tokens.next()
-                .ok_or(ParseError::Expected("policy range (lower bound)"))?,
-            tokens.next()
-                .ok_or(ParseError::Expected("policy range (upper bound)"))?
-        )
-    };
+// prints one record per line, malformed lines included
+fn explain_lines<R: Rule>(
+    input: &str,
+    parse: impl Fn(&str) -> Result<(R, &str), ParseError>,
+    format: Format,
+) {
+    if let Some(header) = format.header() {
+        println!("{}", header);
+    }
+    for (i, line) in input.lines().enumerate() {
+        let record = match parse(line) {
+            Ok((rule, password)) => {
+                let verdict = rule.explain(password);
+                Record {
+                    line: i + 1,
+                    policy: rule.describe(),
+                    password,
+                    verdict: if verdict.valid { "valid" } else { "invalid" },
+                    reason: verdict.reason,
+                }
+            }
+            Err(e) => Record {
+                line: i + 1,
+                policy: String::new(),
+                password: "",
+                verdict: "malformed",
+                reason: format!("column {}: {}", e.column(), e),
+            },
+        };
+        println!("{}", format.format(&record));
+    }
+}
+
+fn parse_line(s: &str) -> Result<(LinePolicy, &str), ParseError> {
+    parse_line_in(s)
+}
+
+// parses a line with the policy letter counted in `L`s
+fn parse_line_in<L: Letter>(s: &str) -> Result<(LinePolicy<L>, &str), ParseError> {
+    peg::parser! {
+        grammar parser() for str {
+            // every failure is labelled with one of the `ParseError::Expected` messages
+            // bounds come with their offset, to point at the one that is out of range
+            // the letter is checked by the caller, who knows whether it's a byte or a char
+            pub(crate) rule line()
+                -> ((usize, &'input str), (usize, &'input str), (usize, &'input str), &'input str)
+                = min:positioned(<lower()>) (quiet!{"-"} / expected!("policy range (upper bound)"))
+                  max:positioned(<upper()>) separator() letter:letter()
+                  (quiet!{":"} / expected!("password")) " "* password:password() ![_]
+                  { (min, max, letter, password) }
 
-    let positions= [min.parse::<usize>().unwrap() - 1, max.parse::<usize>().unwrap() - 1];
+            rule positioned<T>(r: rule<T>) -> (usize, T)
+                = offset:position!() n:r() { (offset, n) }
+
+            rule separator() = quiet!{" "+} / expected!("policy byte")
+
+            // bounds too large for a usize are left to `to_bound`
+            rule lower() -> &'input str
+                = number() / expected!("policy range (lower bound)")
+
+            rule upper() -> &'input str
+                = number() / expected!("policy range (upper bound)")
+
+            rule number() -> &'input str = $(quiet!{['0'..='9']+})
+
+            rule letter() -> (usize, &'input str)
+                = offset:position!() l:$(quiet!{(!([':' | ' ']) [_])*}) { (offset, l) }
+
+            rule password() -> &'input str
+                = p:$([_]*) { p.trim_end() }
+        }
+    }
 
-    // let range = (min.parse()?)..=(max.parse()?);
+    let (min, max, (offset, letter), password) = parser::line(s).map_err(|e| ParseError::Expected {
+        // the grammar labels every failure point exactly once, but stay deterministic anyway
+        expected: e.expected.tokens().min().unwrap_or("password policy"),
+        column: e.location.column,
+    })?;
 
-    println!("{}: {}, {}, {}", policy, min, max, password);
+    let bounds = [
+        to_bound(s, min, "policy range (lower bound)")?,
+        to_bound(s, max, "policy range (upper bound)")?,
+    ];
+    let letter = to_letter(s, offset, letter)?;
 
-    Ok((PasswordPolicy {
-        positions,
-        byte,
+    Ok((LinePolicy {
+        bounds,
+        letter,
     }, password))
 }
 
-impl PasswordPolicy {
+impl<L: Letter> FromStr for PolicyLine<L> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (policy, password) = parse_line_in(s)?;
+        Ok(Self {
+            policy,
+            password: password.into(),
+        })
+    }
+}
+
+impl<L: Letter> fmt::Display for LinePolicy<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{} {}", self.bounds[0].n, self.bounds[1].n, self.letter.to_char())
+    }
+}
+
+impl<L: Letter> fmt::Display for PolicyLine<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.policy, self.password)
+    }
+}
+
+// the column of a byte offset into `s`
+fn column(s: &str, offset: usize) -> usize {
+    s[..offset].chars().count() + 1
+}
+
+// parses a bound found at `offset` of `s`, pointing at its start when it's too large,
+// like `Bound::to_position` does when it's zero
+fn to_bound(
+    s: &str,
+    (offset, n): (usize, &str),
+    expected: &'static str,
+) -> Result<Bound, ParseError> {
+    let column = column(s, offset);
+    n.parse()
+        .map(|n| Bound { n, column })
+        .or(Err(ParseError::Expected { expected, column }))
+}
+
+impl Bound {
+    // the 0-based position of a bound that stands for a 1-based one
+    fn to_position(self) -> Result<usize, ParseError> {
+        self.n.checked_sub(1).ok_or(ParseError::ZeroPosition { column: self.column })
+    }
+}
+
+impl<L> LinePolicy<L> {
+    // a policy with its bounds in the columns `Display` writes them at
+    fn new(letter: L, [min, max]: [usize; 2]) -> Self {
+        let bounds = [
+            Bound { n: min, column: 1 },
+            Bound { n: max, column: min.to_string().len() + 2 },
+        ];
+        Self { letter, bounds }
+    }
+}
+
+fn to_letter<L: Letter>(s: &str, offset: usize, letter: &str) -> Result<L, ParseError> {
+    L::from_str(letter).ok_or(ParseError::Expected {
+        expected: L::EXPECTED,
+        column: column(s, offset),
+    })
+}
+
+impl<L: Letter> PasswordPolicy<L> {
     fn is_valid(&self, password: &str) -> bool {
         self.positions.iter()
             .copied()
-            .filter(|&index| password.as_bytes()[index] == self.byte)
+            // a position past the end of the password just doesn't match
+            .filter(|&index| L::letters(password).nth(index) == Some(self.letter))
             .count() == 1
     }
+
+    fn explain(&self, password: &str) -> Verdict {
+        let mut matches = 0;
+        let mut reasons: Vec<String> = self.positions.iter()
+            .map(|&index| match L::letters(password).nth(index) {
+                Some(l) => {
+                    if l == self.letter {
+                        matches += 1;
+                    }
+                    format!("position {} has {}", index + 1, l.describe())
+                }
+                None => format!("position {} is past the end", index + 1),
+            })
+            .collect();
+        reasons.push(match matches {
+            0 => "no match",
+            1 => "exactly one match",
+            _ => "both match",
+        }.to_string());
+
+        Verdict {
+            valid: matches == 1,
+            reason: reasons.join(", "),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} at exactly one of positions {}, {}",
+            self.letter.describe(), self.positions[0] + 1, self.positions[1] + 1
+        )
+    }
 }
 
-#[derive(thiserror::Error, Debug)]
+impl<L: Letter> CountPolicy<L> {
+    fn is_valid(&self, password: &str) -> bool {
+        self.range.contains(
+            &L::letters(password)
+                .filter(|&l| l == self.letter)
+                .count()
+        )
+    }
+
+    fn explain(&self, password: &str) -> Verdict {
+        let count = L::letters(password).filter(|&l| l == self.letter).count();
+        let valid = self.range.contains(&count);
+        Verdict {
+            valid,
+            reason: format!(
+                "{} occurs {} times, {} {} and {}",
+                self.letter.describe(),
+                count,
+                if valid { "between" } else { "not between" },
+                self.range.start(),
+                self.range.end(),
+            ),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} occurs {} to {} times",
+            self.letter.describe(), self.range.start(), self.range.end()
+        )
+    }
+}
+
+impl<L: Letter> PositionPolicy<L> {
+    fn is_valid(&self, password: &str) -> bool {
+        L::letters(password).nth(self.position) == Some(self.letter)
+    }
+
+    fn explain(&self, password: &str) -> Verdict {
+        match L::letters(password).nth(self.position) {
+            Some(l) => Verdict {
+                valid: l == self.letter,
+                reason: format!("position {} has {}", self.position + 1, l.describe()),
+            },
+            None => Verdict {
+                valid: false,
+                reason: format!("position {} is past the end", self.position + 1),
+            },
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("{} at position {}", self.letter.describe(), self.position + 1)
+    }
+}
+
+impl<L: Letter> Policy<L> {
+    // a count can be 0, a position can't
+    fn new(policy: LinePolicy<L>, semantics: Semantics) -> Result<Self, ParseError> {
+        let LinePolicy { letter, bounds: [min, max] } = policy;
+        Ok(match semantics {
+            Semantics::Count => Self::Count(CountPolicy { letter, range: min.n..=max.n }),
+            Semantics::Positions => Self::Positions(PasswordPolicy {
+                letter,
+                positions: [min.to_position()?, max.to_position()?],
+            }),
+        })
+    }
+}
+
+impl<L: Letter> Rule for Policy<L> {
+    fn is_valid(&self, password: &str) -> bool {
+        match self {
+            Policy::Count(policy) => policy.is_valid(password),
+            Policy::Positions(policy) => policy.is_valid(password),
+            Policy::Position(policy) => policy.is_valid(password),
+        }
+    }
+
+    fn explain(&self, password: &str) -> Verdict {
+        match self {
+            Policy::Count(policy) => policy.explain(password),
+            Policy::Positions(policy) => policy.explain(password),
+            Policy::Position(policy) => policy.explain(password),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Policy::Count(policy) => policy.describe(),
+            Policy::Positions(policy) => policy.describe(),
+            Policy::Position(policy) => policy.describe(),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
 enum ParseError {
-    #[error("expected {0}")]
-    Expected(&'static str),
+    // `column` is 1-based and counted in characters
+    #[error("expected {expected}")]
+    Expected { expected: &'static str, column: usize },
+
+    #[error("expected policy positions to start at 1, got 0")]
+    ZeroPosition { column: usize },
+
+    #[error("expected the line to be valid UTF-8")]
+    InvalidUtf8 { column: usize },
+}
+
+impl ParseError {
+    /// The column of the line at which parsing failed
+    fn column(&self) -> usize {
+        match self {
+            ParseError::Expected { column, .. } => *column,
+            ParseError::ZeroPosition { column } => *column,
+            ParseError::InvalidUtf8 { column } => *column,
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use super::{PasswordPolicy, parse_line};
+    use super::{
+        CountPolicy, Letter, LinePolicy, ParseError, PasswordPolicy, Policy, PolicyLine, Rule,
+        Semantics, Verdict, parse_line, parse_line_in};
+    use proptest::prelude::*;
+
+    fn parse_line_as<L: Letter>(s: &str, semantics: Semantics) -> (Policy<L>, &str) {
+        let (policy, password) = parse_line_in(s).unwrap();
+        (Policy::new(policy, semantics).unwrap(), password)
+    }
 
     #[test]
     fn test_is_valid() {
         let pp = PasswordPolicy {
             positions: [0, 2],
-            byte: b'a',
+            letter: b'a',
         };
-        assert_eq!(pp.is_valid("abcde"), true, "'a' in position 1");
-        assert_eq!(pp.is_valid("bcade"), true, "'a' in position 3");
-        assert_eq!(pp.is_valid("food"), false, "no 'a' whatsoever");
-        assert_eq!(pp.is_valid("abacus"), false, "'a' in both positions");
+        assert!(pp.is_valid("abcde"), "'a' in position 1");
+        assert!(pp.is_valid("bcade"), "'a' in position 3");
+        assert!(!pp.is_valid("food"), "no 'a' whatsoever");
+        assert!(!pp.is_valid("abacus"), "'a' in both positions");
+        assert!(pp.is_valid("ab"), "'a' in position 1, password too short for position 3");
+        assert!(!pp.is_valid(""), "empty password");
     }
 
     #[test]
     fn test_parse() {
         assert_eq!(
             parse_line("1-3 a: banana").unwrap(),
-            (
-                PasswordPolicy {
-                    positions: [1, 3],
-                    byte: b'a',
-                }, "banana"
-            )
+            (LinePolicy::new(b'a', [1, 3]), "banana")
+        );
+        assert_eq!(
+            parse_line("01-003 a: banana").unwrap().0.bounds.map(|b| (b.n, b.column)),
+            [(1, 1), (3, 4)]
         );
 
         assert_eq!(
@@ -121,4 +664,247 @@ mod tests {
             "expected password policy byte to be exactly 1 byte"
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |s| parse_line(s).unwrap_err();
+        assert_eq!(err("1-3 a"), ParseError::Expected { expected: "password", column: 6 });
+        assert_eq!(
+            err("1-3 : banana"),
+            ParseError::Expected {
+                expected: "password policy byte to be exactly 1 byte",
+                column: 5,
+            }
+        );
+        assert_eq!(
+            err("1-3 ab: banana"),
+            ParseError::Expected {
+                expected: "password policy byte to be exactly 1 byte",
+                column: 5,
+            }
+        );
+        assert_eq!(
+            err("x-3 a: banana"),
+            ParseError::Expected { expected: "policy range (lower bound)", column: 1 }
+        );
+        assert_eq!(
+            err("1-y a: banana"),
+            ParseError::Expected { expected: "policy range (upper bound)", column: 3 }
+        );
+        assert_eq!(
+            err("1 3 a: banana"),
+            ParseError::Expected { expected: "policy range (upper bound)", column: 2 }
+        );
+        assert_eq!(err("1-3"), ParseError::Expected { expected: "policy byte", column: 4 });
+        assert_eq!(err("12-34 é: b").column(), 7, "multi-byte characters are not bytes");
+    }
+
+    #[test]
+    fn test_parse_no_panics() {
+        let err = |s| parse_line(s).unwrap_err();
+        let positions = |s| {
+            Policy::new(parse_line(s).unwrap().0, Semantics::Positions).unwrap_err()
+        };
+        assert_eq!(positions("0-3 a: abc"), ParseError::ZeroPosition { column: 1 });
+        assert_eq!(positions("10-0 a: abc"), ParseError::ZeroPosition { column: 4 });
+        assert_eq!(
+            err("99999999999999999999999-3 a: abc"),
+            ParseError::Expected { expected: "policy range (lower bound)", column: 1 }
+        );
+        assert_eq!(
+            err("1-99999999999999999999999 a: abc"),
+            ParseError::Expected { expected: "policy range (upper bound)", column: 3 }
+        );
+
+        let (policy, password) = parse_line_as::<u8>("5-99 a: abc", Semantics::Positions);
+        assert!(!policy.is_valid(password), "both positions past the end");
+        let (policy, password) = parse_line_as::<u8>("1-3 a:", Semantics::Positions);
+        assert!(!policy.is_valid(password), "empty password");
+    }
+
+    #[test]
+    fn test_count_policy() {
+        let pp = CountPolicy {
+            range: 1..=3,
+            letter: b'a',
+        };
+        assert!(pp.is_valid("abcde"), "one 'a'");
+        assert!(pp.is_valid("banana"), "three 'a's");
+        assert!(!pp.is_valid("cdefg"), "no 'a' whatsoever");
+        assert!(!pp.is_valid("aaaa"), "too many 'a's");
+    }
+
+    #[test]
+    fn test_parse_as() {
+        assert_eq!(
+            parse_line_as("1-3 a: banana", Semantics::Count),
+            (Policy::Count(CountPolicy { range: 1..=3, letter: b'a' }), "banana")
+        );
+        assert_eq!(
+            parse_line_as("1-3 a: banana", Semantics::Positions),
+            (Policy::Positions(PasswordPolicy { positions: [0, 2], letter: b'a' }), "banana")
+        );
+
+        // the examples from the puzzle, valid under one semantics but not the other
+        let valid = |s, semantics| {
+            let (policy, password) = parse_line_as::<u8>(s, semantics);
+            policy.is_valid(password)
+        };
+        assert!(valid("1-3 a: abcde", Semantics::Count));
+        assert!(!valid("1-3 b: cdefg", Semantics::Count));
+        assert!(valid("2-9 c: ccccccccc", Semantics::Count));
+        assert!(valid("1-3 a: abcde", Semantics::Positions));
+        assert!(!valid("1-3 b: cdefg", Semantics::Positions));
+        assert!(!valid("2-9 c: ccccccccc", Semantics::Positions));
+
+        // counts start at 0, positions at 1
+        assert_eq!(
+            parse_line_as("0-3 a: bbb", Semantics::Count),
+            (Policy::Count(CountPolicy { range: 0..=3, letter: b'a' }), "bbb")
+        );
+        assert!(valid("0-3 a: bbb", Semantics::Count));
+        assert!(!valid("0-0 b: bbb", Semantics::Count));
+    }
+
+    #[test]
+    fn test_char_policies() {
+        let (policy, password) = parse_line_as::<char>("2-4 é: àéèé", Semantics::Positions);
+        assert_eq!(policy, Policy::Positions(PasswordPolicy { positions: [1, 3], letter: 'é' }));
+        assert!(!policy.is_valid(password), "'é' in both positions");
+        assert!(policy.is_valid("éé"), "'é' in position 2, password too short for position 4");
+
+        // the same line counted in bytes: 'é' is two of them
+        assert_eq!(
+            parse_line("2-4 é: àéèé").unwrap_err(),
+            ParseError::Expected {
+                expected: "password policy byte to be exactly 1 byte",
+                column: 5,
+            }
+        );
+        assert_eq!(
+            parse_line_in::<char>("2-4 éa: x").unwrap_err().to_string(),
+            "expected password policy character to be exactly 1 character"
+        );
+
+        let valid = |s, semantics| {
+            let (policy, password) = parse_line_as::<char>(s, semantics);
+            policy.is_valid(password)
+        };
+        assert!(valid("1-2 ü: üxü", Semantics::Count));
+        assert!(!valid("3-4 ü: üxü", Semantics::Count));
+        assert!(valid("1-3 ✓: ✓✓✗", Semantics::Positions));
+        assert!(!valid("1-2 ✓: ✓✓✗", Semantics::Positions));
+    }
+
+    #[test]
+    fn test_explain() {
+        let explain = |s, semantics| {
+            let (policy, password) = parse_line_as::<u8>(s, semantics);
+            (policy.describe(), policy.explain(password))
+        };
+        assert_eq!(
+            explain("1-4 j: tjjx", Semantics::Positions),
+            (
+                "'j' at exactly one of positions 1, 4".to_string(),
+                Verdict {
+                    valid: false,
+                    reason: "position 1 has 't', position 4 has 'x', no match".into(),
+                },
+            )
+        );
+        assert_eq!(
+            explain("2-9 j: tjjx", Semantics::Positions).1,
+            Verdict {
+                valid: true,
+                reason: "position 2 has 'j', position 9 is past the end, exactly one match".into(),
+            }
+        );
+        assert_eq!(
+            explain("3-4 j: tjjj", Semantics::Positions).1.reason,
+            "position 3 has 'j', position 4 has 'j', both match"
+        );
+        assert_eq!(
+            explain("1-3 j: tjjj", Semantics::Count),
+            (
+                "'j' occurs 1 to 3 times".to_string(),
+                Verdict { valid: true, reason: "'j' occurs 3 times, between 1 and 3".into() },
+            )
+        );
+        assert_eq!(
+            explain("1-2 j: tjjj", Semantics::Count).1.reason,
+            "'j' occurs 3 times, not between 1 and 2"
+        );
+
+        let (policy, password) = parse_line_as::<char>("1-2 é: éa", Semantics::Positions);
+        assert_eq!(
+            policy.explain(password).reason,
+            "position 1 has 'é', position 2 has 'a', exactly one match"
+        );
+        let (policy, password) = parse_line_as::<u8>("1-2 a: éa", Semantics::Positions);
+        assert_eq!(
+            policy.explain(password).reason,
+            "position 1 has byte 0xc3, position 2 has byte 0xa9, no match"
+        );
+    }
+
+    #[test]
+    fn test_explain_agrees_with_is_valid() {
+        for line in include_str!("input.txt").lines().take(200) {
+            for semantics in [Semantics::Count, Semantics::Positions] {
+                let (policy, password) = parse_line_as::<u8>(line, semantics);
+                assert_eq!(policy.explain(password).valid, policy.is_valid(password), "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_policy_line() {
+        let line: PolicyLine = "1-3 a: banana".parse().unwrap();
+        assert_eq!(
+            line,
+            PolicyLine {
+                policy: LinePolicy::new(b'a', [1, 3]),
+                password: "banana".into(),
+            }
+        );
+        assert_eq!(line.to_string(), "1-3 a: banana");
+        let line: PolicyLine = "0-3 a: x".parse().unwrap();
+        assert_eq!(line.to_string(), "0-3 a: x", "a count from 0");
+        assert_eq!("1-3 a".parse::<PolicyLine>(), Err(parse_line("1-3 a").unwrap_err()));
+
+        let line: PolicyLine<char> = "10-200 é: àé".parse().unwrap();
+        assert_eq!(line.to_string(), "10-200 é: àé");
+
+        for s in include_str!("input.txt").lines() {
+            assert_eq!(s.parse::<PolicyLine>().unwrap().to_string(), s, "input lines round-trip");
+        }
+    }
+
+    // passwords that survive the parser as they are: no leading spaces, no trailing whitespace
+    const PASSWORD: &str = "(\\S(.*\\S)?)?";
+
+    proptest! {
+        #[test]
+        fn test_round_trip_bytes(
+            bounds in [0..=usize::MAX, 0..=usize::MAX],
+            // bytes only read back as themselves if they're ASCII
+            letter in any::<u8>().prop_filter("ASCII but not a separator", |b| {
+                b.is_ascii() && *b != b':' && *b != b' '
+            }),
+            password in PASSWORD,
+        ) {
+            let line = PolicyLine { policy: LinePolicy::new(letter, bounds), password };
+            prop_assert_eq!(line.to_string().parse::<PolicyLine>(), Ok(line));
+        }
+
+        #[test]
+        fn test_round_trip_chars(
+            bounds in [0..=usize::MAX, 0..=usize::MAX],
+            letter in any::<char>().prop_filter("not a separator", |c| *c != ':' && *c != ' '),
+            password in PASSWORD,
+        ) {
+            let line = PolicyLine { policy: LinePolicy::new(letter, bounds), password };
+            prop_assert_eq!(line.to_string().parse::<PolicyLine<char>>(), Ok(line));
+        }
+    }
+}
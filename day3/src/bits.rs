@@ -0,0 +1,304 @@
+use std::{fmt, ops::Range};
+
+use super::{
+    grid::{self, ParseError},
+    Vec2,
+};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// A rectangular grid of bits, one per cell, row after row. Every row starts on a
+// word of its own, so that rows can be counted a word at a time; the bits past the
+// end of a row are never set. The same bits are kept column after column too, for
+// counting down a column a word at a time: that takes twice the room of a single
+// copy, and every `set` writes to both.
+pub(crate) struct BitGrid {
+    size: Vec2,
+    // words per row
+    stride: usize,
+    words: Vec<u64>,
+    // words per column
+    column_stride: usize,
+    columns: Vec<u64>,
+}
+
+impl BitGrid {
+    pub(crate) fn new(size: Vec2) -> Self {
+        let stride = (size.x.max(0) as usize).div_ceil(WORD_BITS);
+        let column_stride = (size.y.max(0) as usize).div_ceil(WORD_BITS);
+        Self {
+            size,
+            stride,
+            words: vec![0; stride * size.y.max(0) as usize],
+            column_stride,
+            columns: vec![0; column_stride * size.x.max(0) as usize],
+        }
+    }
+
+    // see `grid::parse`
+    pub(crate) fn parse(input: &str, to_bit: impl Fn(char) -> Option<bool>) -> Result<Self, ParseError> {
+        grid::parse(input, to_bit, Self::new, Self::set)
+    }
+
+    pub(crate) fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    // the room the grid takes, in bits, both copies of them
+    pub(crate) fn bits(&self) -> usize {
+        (self.words.len() + self.columns.len()) * WORD_BITS
+    }
+
+    // the index of a cell, counted the way a `Grid` counts them, None for positions
+    // off the grid
+    pub(crate) fn index(&self, pos: Vec2) -> Option<usize> {
+        grid::index(self.size, pos)
+    }
+
+    // the word a cell is in, and its bit in there
+    fn bit(&self, pos: Vec2) -> Option<(usize, u64)> {
+        self.index(pos)?;
+        let (x, y) = (pos.x as usize, pos.y as usize);
+        Some((y * self.stride + x / WORD_BITS, 1 << (x % WORD_BITS)))
+    }
+
+    // the same in the columns, for a cell on the grid
+    fn column_bit(&self, pos: Vec2) -> (usize, u64) {
+        let (x, y) = (pos.x as usize, pos.y as usize);
+        (x * self.column_stride + y / WORD_BITS, 1 << (y % WORD_BITS))
+    }
+
+    pub(crate) fn get(&self, pos: Vec2) -> Option<bool> {
+        self.bit(pos).map(|(word, mask)| self.words[word] & mask != 0)
+    }
+
+    // setting a cell off the grid does nothing
+    pub(crate) fn set(&mut self, pos: Vec2, bit: bool) {
+        let Some((word, mask)) = self.bit(pos) else {
+            return;
+        };
+        let (column_word, column_mask) = self.column_bit(pos);
+        if bit {
+            self.words[word] |= mask;
+            self.columns[column_word] |= column_mask;
+        } else {
+            self.words[word] &= !mask;
+            self.columns[column_word] &= !column_mask;
+        }
+    }
+
+    // how many cells are set in `rows`, the ones off the grid left out
+    pub(crate) fn count_rows(&self, rows: Range<i64>) -> usize {
+        let start = rows.start.clamp(0, self.size.y) as usize;
+        let end = (rows.end.clamp(0, self.size.y) as usize).max(start);
+        self.words[start * self.stride..end * self.stride]
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    // the columns of the cells set in row `y`, left to right
+    pub(crate) fn row_ones(&self, y: i64) -> impl Iterator<Item = i64> + '_ {
+        let words = if (0..self.size.y).contains(&y) {
+            let start = y as usize * self.stride;
+            &self.words[start..start + self.stride]
+        } else {
+            &[]
+        };
+        words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let x = (i * WORD_BITS) as i64 + word.trailing_zeros() as i64;
+                    // done with the lowest bit that is set
+                    word &= word - 1;
+                    x
+                })
+            })
+        })
+    }
+
+    // How many cells are set in column `x` on rows `start`, `start + step`, and so on
+    // to the bottom, the ones off the grid left out. Steps of up to a word take a
+    // single mask and popcount per word of the column, however many rows are in there.
+    pub(crate) fn count_column(&self, x: i64, start: i64, step: i64) -> usize {
+        if !(0..self.size.x).contains(&x) || step <= 0 {
+            return 0;
+        }
+        let column = &self.columns[x as usize * self.column_stride..][..self.column_stride];
+        // the first row on the grid, past the ones above it
+        let y = if start < 0 { start.rem_euclid(step) } else { start } as usize;
+        let step = step as usize;
+        if step > WORD_BITS {
+            // a row per word at most
+            let height = self.size.y.max(0) as usize;
+            return (y..height)
+                .step_by(step)
+                .filter(|&y| column[y / WORD_BITS] >> (y % WORD_BITS) & 1 != 0)
+                .count();
+        }
+
+        // the rows of a word, when the first one is the word's first bit
+        let rows = (0..WORD_BITS).step_by(step).fold(0u64, |rows, y| rows | 1 << y);
+        // where the first row is in the next word, always within a step of its start
+        let mut offset = y % WORD_BITS % step;
+        let shift = (step - WORD_BITS % step) % step;
+        // the first word leaves out the rows above `y`
+        let mut from_y = !0 << (y % WORD_BITS);
+        let mut count = 0;
+        for &word in column.get(y / WORD_BITS..).unwrap_or_default() {
+            count += (word & from_y & rows << offset).count_ones() as usize;
+            from_y = !0;
+            offset += shift;
+            if offset >= step {
+                offset -= step;
+            }
+        }
+        count
+    }
+
+    // How many of `positions` are set, the ones off the grid counting as unset, a cell
+    // at a time. For trips whose steps come back to the same columns at regular rows,
+    // `count_column` is quicker.
+    pub(crate) fn count_at(&self, positions: impl IntoIterator<Item = Vec2>) -> usize {
+        positions.into_iter().filter(|&pos| self.get(pos) == Some(true)).count()
+    }
+
+    // renders the grid one row per line, every bit going through `to_char`
+    pub(crate) fn display<'a>(&'a self, to_char: impl Fn(bool) -> char + 'a) -> impl fmt::Display + 'a {
+        grid::display(self.size, move |pos| to_char(self.get(pos) == Some(true)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::BitGrid;
+    use crate::{grid::Grid, residue::trees_by_columns, tests::forest, Tile, Topology, Vec2};
+
+    fn bit(c: char) -> Option<bool> {
+        match c {
+            '0' => Some(false),
+            '1' => Some(true),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_get_set() {
+        let mut g = BitGrid::new((70, 3).into());
+        assert_eq!(g.stride, 2, "a row takes two words");
+        g.set((69, 1).into(), true);
+        g.set((70, 1).into(), true);
+        g.set((3, 0).into(), true);
+        assert_eq!(g.get((69, 1).into()), Some(true));
+        assert_eq!(g.get((3, 0).into()), Some(true));
+        assert_eq!(g.get((4, 0).into()), Some(false));
+        assert_eq!(g.get((70, 1).into()), None, "off the grid, nothing was set");
+        assert_eq!(g.words.iter().map(|w| w.count_ones()).sum::<u32>(), 2);
+        g.set((3, 0).into(), false);
+        assert_eq!(g.get((3, 0).into()), Some(false));
+        assert_eq!(g.index((69, 1).into()), Some(139));
+    }
+
+    #[test]
+    fn test_parse() {
+        let g = BitGrid::parse("101\r\n011\n", bit).unwrap();
+        assert_eq!(g.size(), (3, 2).into());
+        assert_eq!(g.get((1, 1).into()), Some(true));
+        assert_eq!(g.display(|b| if b { '#' } else { '.' }).to_string(), "#.#\n.##\n");
+        assert!(BitGrid::parse("10\n1x", bit).is_err());
+    }
+
+    #[test]
+    fn test_count() {
+        let row = "1".repeat(65) + "0" + "1";
+        let g = BitGrid::parse(&format!("{}\n{}\n", "0".repeat(67), row), bit).unwrap();
+        assert_eq!(g.count_rows(0..2), 66);
+        assert_eq!(g.count_rows(1..2), 66);
+        assert_eq!(g.count_rows(0..1), 0);
+        assert_eq!(g.count_rows(-5..50), 66, "rows off the grid count for nothing");
+        assert_eq!(g.count_rows(1..1), 0);
+
+        assert_eq!(g.row_ones(1).collect::<Vec<_>>(), (0..65).chain([66]).collect::<Vec<_>>());
+        assert_eq!(g.row_ones(0).count(), 0);
+        assert_eq!(g.row_ones(2).count(), 0, "off the grid");
+
+        let along = |dx: i64| (0..200).map(move |x| Vec2::from((x * dx, 1)));
+        assert_eq!(g.count_at(along(1)), 66, "off the grid included");
+        assert_eq!(g.count_at(along(2)), 34);
+        assert_eq!(g.count_at(along(0)), 200, "the same cell, again and again");
+        assert_eq!(g.count_at(std::iter::empty()), 0);
+
+        // a column 200 rows high, set every third row from the top
+        let mut g = BitGrid::new((2, 200).into());
+        for y in (0..200).step_by(3) {
+            g.set((1, y).into(), true);
+        }
+        assert_eq!(g.count_column(1, 0, 1), 67);
+        assert_eq!(g.count_column(1, 0, 3), 67);
+        assert_eq!(g.count_column(1, 1, 3), 0);
+        assert_eq!(g.count_column(1, 63, 6), 23, "from the last bit of a word");
+        assert_eq!(g.count_column(1, 0, 99), 3);
+        assert_eq!(g.count_column(1, -3, 66), 3, "rows above the grid left out");
+        assert_eq!(g.count_column(1, 200, 1), 0);
+        assert_eq!(g.count_column(0, 0, 1), 0);
+        assert_eq!(g.count_column(2, 0, 1), 0, "off the grid");
+        g.set((1, 63).into(), false);
+        assert_eq!(g.count_column(1, 0, 3), 66);
+    }
+
+    // Against a `Grid<Tile>`, a byte per tile: the trees of a large forest, all of them
+    // and along the puzzle's slopes. Run it with
+    // `cargo test --release -- --ignored --nocapture bench_layouts`.
+    #[test]
+    #[ignore]
+    fn bench_layouts() {
+        let size: Vec2 = (31, 1 << 23).into();
+        let bits = forest(size, 0x2545_f491_4f6c_dd1d).grid;
+        let mut bytes = Grid::new(size);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                bytes.set((x, y).into(), Tile::from_bit(bits.get((x, y).into()) == Some(true)));
+            }
+        }
+
+        let time = |f: &dyn Fn() -> usize| {
+            let start = Instant::now();
+            let count = f();
+            (count, start.elapsed())
+        };
+        let compare = |what: &str, bytes: (usize, Duration), bits: (usize, Duration)| {
+            let ((trees, byte_time), (n, bit_time)) = (bytes, bits);
+            assert_eq!(trees, n, "{}", what);
+            println!(
+                "{}: {} trees, {:?} with a byte per tile, {:?} with a bit, {:.1}x",
+                what, trees, byte_time, bit_time,
+                byte_time.as_secs_f64() / bit_time.as_secs_f64()
+            );
+        };
+
+        let cells = || (0..size.y).flat_map(|y| (0..size.x).map(move |x| Vec2::from((x, y))));
+        compare(
+            "every row",
+            time(&|| cells().filter(|&pos| bytes.get(pos) == Some(&Tile::Tree)).count()),
+            time(&|| bits.count_rows(0..size.y)),
+        );
+
+        // a word of each column at a time, against a step at a time
+        for delta in [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)].map(Vec2::from) {
+            let topology = Topology::default();
+            let trip = || {
+                let len = topology.trip_len(delta, size) as i64;
+                (0..len).filter_map(move |n| topology.normalize(delta * n, size))
+            };
+            compare(
+                &format!("right {}, down {}", delta.x, delta.y),
+                time(&|| trip().filter(|&pos| bytes.get(pos) == Some(&Tile::Tree)).count()),
+                time(&|| trees_by_columns(&bits, delta)),
+            );
+        }
+    }
+}
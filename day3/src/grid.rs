@@ -0,0 +1,220 @@
+use std::fmt;
+
+use super::Vec2;
+
+// A rectangular grid of cells in flat, row-major storage. Positions outside of it
+// simply don't exist: anything smarter is up to whoever wraps the grid.
+pub(crate) struct Grid<T> {
+    size: Vec2,
+    cells: Vec<T>,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub(crate) enum ParseError {
+    // rows and columns are 1-based, columns are counted in characters
+    #[error("row {row}, column {column}: unexpected {found:?}")]
+    Cell { row: usize, column: usize, found: char },
+
+    #[error("row {row} has {len} columns, but the first row has {expected}")]
+    Ragged { row: usize, len: usize, expected: usize },
+
+    #[error("the grid has no cells")]
+    Empty,
+}
+
+impl<T: Default> Grid<T> {
+    pub(crate) fn new(size: Vec2) -> Self {
+        let num_cells = size.x * size.y;
+        Self {
+            size,
+            cells: (0..num_cells).map(|_| Default::default()).collect(),
+        }
+    }
+}
+
+// Parses one row per line, every character going through `to_cell`, into whatever
+// `new` makes for a grid of that size. Rows end with "\n" or "\r\n", and blank lines
+// before or after the grid don't make more rows, though rows are still numbered by
+// the line they are on.
+pub(crate) fn parse<G, T>(
+    input: &str,
+    to_cell: impl Fn(char) -> Option<T>,
+    new: impl FnOnce(Vec2) -> G,
+    mut set: impl FnMut(&mut G, Vec2, T),
+) -> Result<G, ParseError> {
+    let lines: Vec<&str> = input
+        .trim_end_matches(['\r', '\n'])
+        .split('\n')
+        .map(|row| row.strip_suffix('\r').unwrap_or(row))
+        .collect();
+    let skipped = lines.iter().take_while(|line| line.is_empty()).count();
+    let rows = &lines[skipped..];
+
+    let Some(columns) = rows.first().map(|row| row.chars().count()) else {
+        return Err(ParseError::Empty);
+    };
+
+    let mut grid = new((columns as i64, rows.len() as i64).into());
+    for (y, line) in rows.iter().enumerate() {
+        let row = y + skipped;
+        let len = line.chars().count();
+        if len != columns {
+            return Err(ParseError::Ragged { row: row + 1, len, expected: columns });
+        }
+        for (col, c) in line.chars().enumerate() {
+            let cell = to_cell(c).ok_or(ParseError::Cell {
+                row: row + 1,
+                column: col + 1,
+                found: c,
+            })?;
+            set(&mut grid, (col as i64, y as i64).into(), cell);
+        }
+    }
+    Ok(grid)
+}
+
+impl<T> Grid<T> {
+    pub(crate) fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    // the index of a cell in our flat storage, None for positions off the grid
+    pub(crate) fn index(&self, pos: Vec2) -> Option<usize> {
+        index(self.size, pos)
+    }
+
+    pub(crate) fn get(&self, pos: Vec2) -> Option<&T> {
+        self.index(pos).map(|i| &self.cells[i])
+    }
+
+    // setting a cell off the grid does nothing
+    pub(crate) fn set(&mut self, pos: Vec2, cell: T) {
+        if let Some(index) = self.index(pos) {
+            self.cells[index] = cell
+        }
+    }
+}
+
+// The index of a cell of a grid of `size`, row after row, None for positions off the
+// grid
+pub(crate) fn index(size: Vec2, pos: Vec2) -> Option<usize> {
+    if pos.x < 0 || pos.x >= size.x || pos.y < 0 || pos.y >= size.y {
+        None
+    } else {
+        Some((pos.x + pos.y * size.x) as _)
+    }
+}
+
+// Renders a grid of `size` one row per line, the cell at every position going
+// through `to_char`
+pub(crate) fn display<'a>(size: Vec2, to_char: impl Fn(Vec2) -> char + 'a) -> impl fmt::Display + 'a {
+    Render { size, to_char }
+}
+
+struct Render<F> {
+    size: Vec2,
+    to_char: F,
+}
+
+impl<F: Fn(Vec2) -> char> fmt::Display for Render<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                write!(f, "{}", (self.to_char)((x, y).into()))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.cells.chunks(self.size.x.max(1) as usize) {
+            for cell in row {
+                write!(f, "{:?}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{display, index, Grid, ParseError};
+
+    fn digit(c: char) -> Option<u32> {
+        c.to_digit(10)
+    }
+
+    // a `Grid` of whatever `to_cell` makes of `input`
+    fn parse<T: Default>(
+        input: &str,
+        to_cell: impl Fn(char) -> Option<T>,
+    ) -> Result<Grid<T>, ParseError> {
+        super::parse(input, to_cell, Grid::new, Grid::set)
+    }
+
+    #[test]
+    fn test_index() {
+        let g = Grid::<u8>::new((3, 5).into());
+        assert_eq!(g.index((0, 0).into()), Some(0));
+        assert_eq!(g.index((2, 0).into()), Some(2));
+        assert_eq!(g.index((0, 1).into()), Some(3));
+        assert_eq!(g.index((2, 1).into()), Some(5));
+        assert_eq!(g.index((3, 0).into()), None);
+        assert_eq!(g.index((0, -1).into()), None);
+        assert_eq!(index((3, 5).into(), (2, 4).into()), Some(14));
+        assert_eq!(index((3, 5).into(), (0, 5).into()), None);
+    }
+
+    #[test]
+    fn test_get_set() {
+        let mut g = Grid::new((2, 2).into());
+        g.set((1, 0).into(), 7);
+        g.set((2, 0).into(), 9);
+        assert_eq!(g.get((1, 0).into()), Some(&7));
+        assert_eq!(g.get((0, 1).into()), Some(&0));
+        assert_eq!(g.get((2, 0).into()), None, "off the grid, nothing was set");
+    }
+
+    #[test]
+    fn test_parse() {
+        let g = parse("123\r\n456\n", digit).unwrap();
+        assert_eq!(g.size(), (3, 2).into());
+        assert_eq!(g.get((2, 1).into()), Some(&6));
+        assert_eq!(format!("{:?}", g), "123\n456\n");
+        let parity = |pos| if g.get(pos).unwrap() % 2 == 0 { 'e' } else { 'o' };
+        assert_eq!(display(g.size(), parity).to_string(), "oeo\neoe\n");
+
+        let g = parse("ab\néc", Some).unwrap();
+        assert_eq!(g.size(), (2, 2).into(), "columns are characters, not bytes");
+        assert_eq!(display(g.size(), |pos| *g.get(pos).unwrap()).to_string(), "ab\néc\n");
+
+        assert_eq!(
+            parse("12\n3x", digit).unwrap_err(),
+            ParseError::Cell { row: 2, column: 2, found: 'x' }
+        );
+        assert_eq!(
+            parse("12\n3", digit).unwrap_err(),
+            ParseError::Ragged { row: 2, len: 1, expected: 2 }
+        );
+        assert_eq!(parse("\n", digit).unwrap_err(), ParseError::Empty);
+        assert_eq!(parse("", digit).unwrap_err(), ParseError::Empty);
+
+        let g = parse("\r\n\n12\n34\n", digit).unwrap();
+        assert_eq!(g.size(), (2, 2).into(), "blank lines before the grid are no rows");
+        assert_eq!(g.get((0, 0).into()), Some(&1));
+        assert_eq!(
+            parse("\n12\n3x", digit).unwrap_err(),
+            ParseError::Cell { row: 3, column: 2, found: 'x' },
+            "but they still count for row numbers"
+        );
+        assert_eq!(
+            parse("\n12\n3", digit).unwrap_err(),
+            ParseError::Ragged { row: 3, len: 1, expected: 2 }
+        );
+    }
+}
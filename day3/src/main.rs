@@ -1,144 +1,243 @@
-use std::{fmt, fmt::{Formatter, write}, ops::AddAssign};
+use std::{
+    cell::RefCell, fmt, fmt::Formatter, fs::File, io, ops::RangeInclusive, path::PathBuf,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Vec2 {
-    x: i64,
-    y: i64,
-}
+use anyhow::Context;
+use bits::BitGrid;
+use grid::ParseError;
+use path::Costs;
+use render::Picture;
+use residue::Residues;
+use search::{Order, SlopeSearch};
+use topology::Topology;
+use vec2::Vec2;
+
+mod bits;
+mod grid;
+mod path;
+mod render;
+mod residue;
+mod search;
+mod topology;
+mod vec2;
 
 struct Map {
-    size: Vec2,
-    tiles: Vec<Tile>,
+    // a bit per tile, set for trees, kept by row and by column
+    grid: BitGrid,
+    topology: Topology,
+    // the last ones `precompute` worked out, for the slopes that share them
+    residues: RefCell<Option<Residues>>,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Default)]
 enum Tile {
+    #[default]
     Open,
     Tree,
 }
 
-impl Default for Tile {
-    fn default() -> Self {
-        Self::Open
+impl Tile {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(Tile::Open),
+            '#' => Some(Tile::Tree),
+            _ => None,
+        }
+    }
+
+    fn from_bit(bit: bool) -> Self {
+        if bit {
+            Tile::Tree
+        } else {
+            Tile::Open
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Tile::Open => '.',
+            Tile::Tree => '#',
+        }
     }
 }
 
 // Debug implementation that writes out a graphical implementation of the tile
 impl fmt::Debug for Tile {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let c = match self {
-            Tile::Open => '.',
-            Tile::Tree => '#',
-        };
-        write!(f, "{}", c)
+        write!(f, "{}", self.to_char())
     }
 }
 
 impl fmt::Debug for Map {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for row in 0..self.size.y {
-            for col in 0..self.size.x {
-                write!(f, "{:?}", self.get((col, row).into()))?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        write!(f, "{}", self.grid.display(|bit| Tile::from_bit(bit).to_char()))
     }
 }
 
 impl Map {
+    // the puzzle only ever parses its map, but an empty one is handy to build on
+    #[allow(dead_code)]
     fn new(size: Vec2) -> Self {
-        let num_tiles = size.x * size.y;
         Self {
-            size,
-            tiles: (0..num_tiles)
-                .into_iter()
-                .map(|_| Default::default())
-                .collect(),
+            grid: BitGrid::new(size),
+            topology: Topology::default(),
+            residues: RefCell::default(),
         }
     }
 
+    // what lies past the edges, the puzzle's horizontal cylinder unless told otherwise
+    fn with_topology(self, topology: Topology) -> Self {
+        Self { topology, ..self }
+    }
+
+    fn size(&self) -> Vec2 {
+        self.grid.size()
+    }
+
     // assume every tile outside the map is immutable
+    #[allow(dead_code)]
     fn set(&mut self, pos: Vec2, tile: Tile) {
-        if let Some(index) = self.index(pos) {
-            self.tiles[index] = tile
+        if let Some(pos) = self.normalize_pos(pos) {
+            self.grid.set(pos, tile == Tile::Tree);
+            *self.residues.get_mut() = None;
         }
     }
 
     fn get(&self, pos: Vec2) -> Tile {
-        self.index(pos).map(|i| self.tiles[i]).unwrap_or_default()
+        self.normalize_pos(pos)
+            .and_then(|pos| self.grid.get(pos))
+            .map(Tile::from_bit)
+            .unwrap_or_default()
     }
 
-    // a helper function to extend our map forever to the right as well as to left
+    // a helper function to extend our map forever in the directions its topology
+    // allows, by default to the right as well as to left
     // it returns None for positions outside of the map
     fn normalize_pos(&self, pos: Vec2) -> Option<Vec2> {
-        if pos.y < 0 || pos.y >= self.size.y {
-            None
-        } else {
-            let x = pos.x % self.size.x;
-
-            let x = if x < 0 {
-                // wrap around for positions to the left of 0
-                self.size.x + x
-            } else { x };
-            Some((x, pos.y).into())
-        }
+        self.topology.normalize(pos, self.size())
     }
 
     // a helper function to return index of a tile in our flat storage
     // it will return None for positions that do not exist on map
     fn index(&self, pos: Vec2) -> Option<usize> {
-        self.normalize_pos(pos)
-            .map(|pos| (pos.x + pos.y * self.size.x) as _)
-    }
-
-    fn parse(input: &[u8]) -> Self {
-        let mut columns = 0;
-        let mut rows = 1;
-        for &c in input.iter() {
-            if c == b'\n' {
-                rows += 1;
-                columns = 0;
-            } else {
-                columns += 1;
-            }
+        self.normalize_pos(pos).and_then(|pos| self.grid.index(pos))
+    }
+
+    // how many of the tiles on the map are `tile`
+    fn count(&self, tile: Tile) -> usize {
+        let trees = self.grid.count_rows(0..self.size().y);
+        match tile {
+            Tile::Tree => trees,
+            Tile::Open => (self.size().x * self.size().y) as usize - trees,
         }
+    }
 
-        let mut iter = input.iter().copied();
-        let mut map = Self::new((columns, rows).into());
-        for row in 0..map.size.y {
-            for col in 0..map.size.x {
-                let tile = match iter.next() {
-                    Some(b'.') => Tile::Open,
-                    Some(b'#') => Tile::Tree,
-                    c => panic!("Expected '.' or '#', but got {:?}", c),
-                };
-                map.set((col, row).into(), tile);
-            }
-            iter.next();
+    // the modulus of the `Residues` that can count the trees along `delta`, if any
+    fn residue_modulus(&self, delta: Vec2) -> Option<i64> {
+        match self.topology {
+            Topology::HorizontalCylinder => residue::modulus(delta, self.size()),
+            _ => None,
         }
-        map
     }
-}
 
+    // Works out the residues for `modulus`, for `count_along` to use on every slope
+    // that shares them. That takes a pass over the whole map, so it's up to the caller
+    // to know it pays off, and only the last ones are kept.
+    fn precompute(&self, modulus: i64) {
+        let mut residues = self.residues.borrow_mut();
+        if residues.as_ref().map(Residues::modulus) != Some(modulus) {
+            *residues = Some(Residues::new(&self.grid, modulus));
+        }
+    }
 
-// Build vec2 from a tuple
-impl From<(i64, i64)> for Vec2 {
-    fn from((x, y): (i64, i64)) -> Self {
-        Self { x, y }
+    // How many of the tiles along `generate_itinerary` are `tile`. With the slope's
+    // residues precomputed, that takes a step per column the slope comes back to
+    // rather than a step per row; without them, down a horizontal cylinder, the rows of
+    // those columns are counted a word at a time.
+    fn count_along(&self, delta: Vec2, tile: Tile) -> usize {
+        let itinerary = generate_itinerary(self, delta);
+        let len = itinerary.len();
+        let residues = self.residues.borrow();
+        let trees = match residues.as_ref() {
+            Some(residues) if self.residue_modulus(delta) == Some(residues.modulus()) => {
+                residues.trees_along(delta)
+            }
+            _ if self.topology == Topology::HorizontalCylinder && delta.y > 0 => {
+                residue::trees_by_columns(&self.grid, delta)
+            }
+            _ => self.grid.count_at(itinerary.filter_map(|pos| self.normalize_pos(pos))),
+        };
+        match tile {
+            Tile::Tree => trees,
+            // tiles off the map are open too
+            Tile::Open => len - trees,
+        }
     }
-}
 
-impl AddAssign for Vec2 {
-    fn add_assign(&mut self, rhs: Self) {
-        self.x += rhs.x;
-        self.y += rhs.y;
+    // "." is an open square, "#" a tree
+    fn parse(input: &str) -> Result<Self, ParseError> {
+        Ok(Self {
+            grid: BitGrid::parse(input, |c| Tile::from_char(c).map(|tile| tile == Tile::Tree))?,
+            topology: Topology::default(),
+            residues: RefCell::default(),
+        })
     }
 }
 
-fn main() {
-    let map = Map::parse(include_bytes!("./input.txt"));
-    dbg!(map.size);
+
+fn main() -> anyhow::Result<()> {
+    let mut topology = Topology::default();
+    // `--search` ranks every slope in range instead of answering the puzzle
+    let (mut search, mut dx, mut dy) = (None, None, None);
+    // `--route` goes from the top row to the bottom one instead
+    let (mut route, mut diagonal, mut tree_cost) = (None, false, None);
+    // `--render` draws the puzzle's slopes into a .ppm, .png or .svg file
+    let (mut render, mut copies, mut scale) = (None, 1, 4);
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("--topology", t)) => {
+                topology = Topology::parse(t).with_context(|| {
+                    format!(
+                        "unknown topology in {:?}, expected 'bounded', 'horizontal-cylinder', \
+                         'vertical-cylinder', 'torus' or 'mirrored'",
+                        arg
+                    )
+                })?
+            }
+            Some(("--search", order)) => {
+                let order = Order::parse(order).with_context(|| {
+                    format!("unknown order in {:?}, expected 'fewest' or 'most'", arg)
+                })?;
+                search = Some(order)
+            }
+            Some(("--dx", range)) => dx = Some(parse_range(&arg, range)?),
+            Some(("--dy", range)) => dy = Some(parse_range(&arg, range)?),
+            Some(("--route", r @ ("bfs" | "dijkstra" | "a-star"))) => route = Some(r.to_string()),
+            Some(("--moves", "4")) => diagonal = false,
+            Some(("--moves", "8")) => diagonal = true,
+            Some(("--render", path)) => render = Some(PathBuf::from(path)),
+            Some(("--copies", n)) => {
+                copies = n.parse().with_context(|| format!("invalid number in {:?}", arg))?
+            }
+            Some(("--scale", n)) => {
+                scale = n.parse().with_context(|| format!("invalid number in {:?}", arg))?
+            }
+            Some(("--tree-cost", cost)) => {
+                tree_cost = Some(cost.parse().with_context(|| format!("invalid cost in {:?}", arg))?)
+            }
+            _ => anyhow::bail!(
+                "unknown argument {:?}, expected '--topology=bounded|horizontal-cylinder|\
+                 vertical-cylinder|torus|mirrored', '--search=fewest|most', '--dx=MIN..MAX', \
+                 '--dy=MIN..MAX', '--route=bfs|dijkstra|a-star', '--moves=4|8', \
+                 '--tree-cost=N', '--render=PATH.ppm|png|svg', '--copies=N' \
+                 or '--scale=N'",
+                arg
+            ),
+        }
+    }
+
+    let map = Map::parse(include_str!("./input.txt"))?.with_topology(topology);
+    dbg!(map.size());
     // from the problem statement
     let deltas: &[Vec2] = &[
         (1, 1).into(),
@@ -147,33 +246,118 @@ fn main() {
         (7, 1).into(),
         (1, 2).into(),
     ];
+    if let Some(path) = render {
+        let file = File::create(&path).with_context(|| format!("could not create {}", path.display()))?;
+        let out = io::BufWriter::new(file);
+        let picture = Picture::new(&map)
+            .with_slopes(deltas)
+            .with_copies(copies)
+            .with_scale(scale);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ppm") => picture.write_ppm(out)?,
+            Some("png") => picture.write_png(out)?,
+            Some("svg") => picture.write_svg(out)?,
+            _ => anyhow::bail!(
+                "unknown image format for {}, expected .ppm, .png or .svg",
+                path.display()
+            ),
+        }
+        return Ok(());
+    }
+    if let Some(order) = search {
+        // every slope that stays on the map for at least two tiles, by default
+        let dx = dx.unwrap_or(0..=map.size().x - 1);
+        let dy = dy.unwrap_or(1..=map.size().y - 1);
+        for (delta, trees) in SlopeSearch::new(&map).rank(dx, dy, order).iter().take(10) {
+            println!("right {}, down {}: {} trees", delta.x, delta.y, trees);
+        }
+        return Ok(());
+    }
+    if let Some(route) = route {
+        let moves: Vec<Vec2> = if diagonal {
+            Vec2::default().neighbors8().collect()
+        } else {
+            Vec2::default().neighbors4().collect()
+        };
+        // without a cost, trees are in the way
+        let costs = Costs { tree: tree_cost };
+        let route = match route.as_str() {
+            "bfs" => path::bfs(&map, &moves),
+            "dijkstra" => path::dijkstra(&map, &moves, costs),
+            _ => path::a_star(&map, &moves, costs),
+        };
+        match route {
+            Some(route) => {
+                println!("{} cells, cost {}", route.path.len(), route.cost);
+                for pos in route.path {
+                    println!("({}, {})", pos.x, pos.y);
+                }
+            }
+            None => println!("no route to the bottom"),
+        }
+        return Ok(());
+    }
+
     let answer = deltas.iter()
         .copied()
-        .map(|delta| generate_itinerary(&map, delta))  // generate all itineraries
-        .map(|itin| {   // count trees
-            itin.into_iter()
-                .filter(|&pos| map.get(pos) == Tile::Tree)
-                .count()
-        })
+        .map(|delta| map.count_along(delta, Tile::Tree))  // count trees
         .product::<usize>();  // multiply everything together
     println!("Answer: {}", answer);
+    Ok(())
 }
 
-fn generate_itinerary(map: &Map, delta: Vec2) -> Vec<Vec2> {
-    let mut pos = Vec2::from((0, 0));
-    let mut res: Vec<_> = Default::default();
+// "MIN..MAX", both included
+fn parse_range(arg: &str, range: &str) -> anyhow::Result<RangeInclusive<i64>> {
+    let parse = |n: &str| n.parse::<i64>().with_context(|| format!("invalid range in {:?}", arg));
+    match range.split_once("..") {
+        Some((min, max)) => Ok(parse(min)?..=parse(max)?),
+        None => anyhow::bail!("invalid range in {:?}, expected 'MIN..MAX'", arg),
+    }
+}
+
+// The positions from the top left corner, `delta` at a time, until the map ends,
+// or until the trip would repeat itself on a map without an end in the direction
+// of `delta`. Positions are not normalized, and only worked out as they are needed.
+#[derive(Debug, Clone)]
+struct Itinerary {
+    pos: Vec2,
+    delta: Vec2,
+    remaining: usize,
+}
 
-    while map.normalize_pos(pos).is_some() {
-        res.push(pos);
-        pos += delta;
+impl Iterator for Itinerary {
+    type Item = Vec2;
+
+    fn next(&mut self) -> Option<Vec2> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let pos = self.pos;
+        self.pos += self.delta;
+        self.remaining -= 1;
+        Some(pos)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Itinerary {}
+
+fn generate_itinerary(map: &Map, delta: Vec2) -> Itinerary {
+    Itinerary {
+        pos: (0, 0).into(),
+        delta,
+        // the map's size and topology are all it takes to know where the trip ends
+        remaining: map.topology.trip_len(delta, map.size()),
     }
-    res
 }
 
 
 #[cfg(test)]
-mod tests {
-    use super::{Vec2, Map, generate_itinerary};
+pub(crate) mod tests {
+    use super::{Vec2, Map, ParseError, Tile, Topology, generate_itinerary};
 
     #[test]
     fn test_tuple() {
@@ -192,6 +376,27 @@ mod tests {
         assert_eq!(m.normalize_pos((-2, 0).into()), Some((0, 0).into()));
         assert_eq!(m.normalize_pos((0, -1).into()), None);
         assert_eq!(m.normalize_pos((0, 2).into()), None);
+
+        let m = Map::new((2, 2).into()).with_topology(Topology::Torus);
+        assert_eq!(m.normalize_pos((0, 2).into()), Some((0, 0).into()));
+        assert_eq!(m.normalize_pos((-1, -1).into()), Some((1, 1).into()));
+        assert_eq!(m.index((3, 3).into()), Some(3));
+        let m = Map::new((2, 2).into()).with_topology(Topology::Bounded);
+        assert_eq!(m.normalize_pos((2, 0).into()), None);
+        assert_eq!(m.index((2, 0).into()), None);
+    }
+
+    #[test]
+    fn test_topology_get_set() {
+        let mut m = Map::new((3, 2).into()).with_topology(Topology::Mirrored);
+        m.set((3, 0).into(), Tile::Tree);
+        assert_eq!(m.get((2, 0).into()), Tile::Tree, "just past the edge is the edge again");
+        assert_eq!(m.get((-3, 0).into()), Tile::Tree);
+
+        let mut m = Map::new((3, 2).into()).with_topology(Topology::Bounded);
+        m.set((3, 0).into(), Tile::Tree);
+        assert_eq!(format!("{:?}", m), "...\n...\n", "nothing past the edge to set");
+        assert_eq!(m.get((3, 0).into()), Tile::Open);
     }
 
     #[test]
@@ -206,7 +411,7 @@ mod tests {
     #[test]
     fn test_generate_itinerary() {
         assert_eq!(
-            &generate_itinerary(&Map::new((5, 5).into()), (1, 1).into()),
+            &generate_itinerary(&Map::new((5, 5).into()), (1, 1).into()).collect::<Vec<_>>(),
             &[
                 (0, 0).into(),
                 (1, 1).into(),
@@ -218,7 +423,7 @@ mod tests {
         );
 
         assert_eq!(
-            &generate_itinerary(&Map::new((5, 5).into()), (3, 1).into()),
+            &generate_itinerary(&Map::new((5, 5).into()), (3, 1).into()).collect::<Vec<_>>(),
             &[
                 (0, 0).into(),
                 (3, 1).into(),
@@ -230,14 +435,129 @@ mod tests {
         );
 
         assert_eq!(
-            &generate_itinerary(&Map::new((5, 5).into()), (2, 2).into()),
+            &generate_itinerary(&Map::new((5, 5).into()), (2, 2).into()).collect::<Vec<_>>(),
             &[(0, 0).into(), (2, 2).into(), (4, 4).into(), ],
             "right 2 down 2, 5x5 map"
         );
         assert_eq!(
-            &generate_itinerary(&Map::new((9, 9).into()), (2, 5).into()),
+            &generate_itinerary(&Map::new((9, 9).into()), (2, 5).into()).collect::<Vec<_>>(),
             &[(0, 0).into(), (2, 5).into(), ],
             "right 2 down 5, 9x9 map"
-        )
+        );
+
+        let m = |topology| Map::new((3, 2).into()).with_topology(topology);
+        assert_eq!(
+            &generate_itinerary(&m(Topology::Bounded), (1, 1).into()).collect::<Vec<_>>(),
+            &[(0, 0).into(), (1, 1).into()]
+        );
+        assert_eq!(
+            generate_itinerary(&m(Topology::Torus), (1, 1).into()).len(),
+            6,
+            "on a torus, the trip goes on until it gets back to the start"
+        );
+        assert_eq!(
+            &generate_itinerary(&m(Topology::HorizontalCylinder), (2, 0).into()).collect::<Vec<_>>(),
+            &[(0, 0).into(), (2, 0).into(), (4, 0).into()],
+            "a horizontal trip around the cylinder"
+        );
+        assert_eq!(
+            generate_itinerary(&m(Topology::Mirrored), (1, 0).into()).len(),
+            6,
+            "there and back again"
+        );
+        assert_eq!(
+            &generate_itinerary(&m(Topology::VerticalCylinder), (0, 0).into()).collect::<Vec<_>>(),
+            &[(0, 0).into()],
+            "standing still"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let map = Map::parse("..#\n#..\n").unwrap();
+        assert_eq!(map.size(), (3, 2).into(), "no phantom row after the last newline");
+        assert_eq!(format!("{:?}", map), "..#\n#..\n");
+        assert_eq!(map.get((2, 0).into()), Tile::Tree);
+        assert_eq!(map.get((0, 1).into()), Tile::Tree);
+
+        for input in ["..#\r\n#..\r\n", "..#\r\n#..", "..#\n#..\n\n\n", "..#\r\n#..\n"] {
+            assert_eq!(format!("{:?}", Map::parse(input).unwrap()), "..#\n#..\n", "{:?}", input);
+        }
+
+        let map = Map::parse(include_str!("./input.txt")).unwrap();
+        assert_eq!(map.size(), (31, 323).into());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |input| Map::parse(input).unwrap_err();
+        assert_eq!(err("..#\n#.x\n"), ParseError::Cell { row: 2, column: 3, found: 'x' });
+        assert_eq!(
+            err("..#\n#.\n...\n"),
+            ParseError::Ragged { row: 2, len: 2, expected: 3 }
+        );
+        assert_eq!(err("..#\n\n...\n"), ParseError::Ragged { row: 2, len: 0, expected: 3 });
+        assert_eq!(err(""), ParseError::Empty);
+        assert_eq!(err("\r\n"), ParseError::Empty);
+        assert_eq!(Map::parse("\n..#\n").unwrap().size(), (3, 1).into(), "a blank line first");
+        assert_eq!(err("..\t\n").to_string(), "row 1, column 3: unexpected '\\t'");
+    }
+
+    // the example from the puzzle
+    pub(crate) const EXAMPLE: &str = "\
+..##.......
+#...#...#..
+.#....#..#.
+..#.#...#.#
+.#...##..#.
+..#.##.....
+.#.#.#....#
+.#........#
+#.##...#...
+#...##....#
+.#..#...#.#
+";
+
+    // a map of `size` with about a tree in four, wherever xorshift from `seed` puts
+    // them
+    pub(crate) fn forest(size: Vec2, seed: u64) -> Map {
+        let mut map = Map::new(size);
+        let mut state = seed;
+        for y in 0..size.y {
+            for x in 0..size.x {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                if state.is_multiple_of(4) {
+                    map.set((x, y).into(), Tile::Tree);
+                }
+            }
+        }
+        map
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_itinerary() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let mut itinerary = generate_itinerary(&map, (3, 1).into());
+        assert_eq!(itinerary.len(), 11);
+        assert_eq!(itinerary.next(), Some((0, 0).into()));
+        assert_eq!(itinerary.next(), Some((3, 1).into()));
+        assert_eq!(itinerary.len(), 9, "counts down as it goes");
+        assert_eq!(itinerary.last(), Some((30, 10).into()));
+        assert_eq!(generate_itinerary(&map, (1, 2).into()).len(), 6);
+    }
+
+    #[test]
+    fn test_count_along() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let trees: Vec<_> = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)]
+            .into_iter()
+            .map(|delta| map.count_along(delta.into(), Tile::Tree))
+            .collect();
+        assert_eq!(trees, [2, 7, 3, 4, 2]);
+        assert_eq!(map.count_along((3, 1).into(), Tile::Open), 11 - 7);
+        assert_eq!(map.count(Tile::Tree), EXAMPLE.matches('#').count());
+        assert_eq!(map.count(Tile::Open), EXAMPLE.matches('.').count());
+    }
+}
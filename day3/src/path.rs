@@ -0,0 +1,283 @@
+// Routes from the top row of a map to its bottom row, moving one step of a move set
+// at a time. Steps wrap around the edges like everything else on the map does.
+//
+// Every cell of a route costs what it takes to cross it, the first one included:
+// an open square costs 1, a tree costs `Costs::tree`, or can't be crossed at all.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use super::{Map, Tile, Vec2};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Route {
+    // normalized positions, from the top row to the bottom one
+    pub(crate) path: Vec<Vec2>,
+    pub(crate) cost: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Costs {
+    // None if trees are in the way
+    pub(crate) tree: Option<u64>,
+}
+
+impl Costs {
+    fn of(self, tile: Tile) -> Option<u64> {
+        match tile {
+            Tile::Open => Some(1),
+            Tile::Tree => self.tree,
+        }
+    }
+
+    // what crossing any cell costs at least
+    fn min(self) -> u64 {
+        self.tree.map_or(1, |tree| tree.min(1))
+    }
+}
+
+// the fewest cells through open squares only
+pub(crate) fn bfs(map: &Map, moves: &[Vec2]) -> Option<Route> {
+    let open = |pos| map.get(pos) == Tile::Open;
+    let mut prev: Vec<Option<Vec2>> = vec![None; cells(map)];
+    let mut seen = vec![false; cells(map)];
+    let mut queue: VecDeque<Vec2> = top_row(map).filter(|&pos| open(pos)).collect();
+    for &pos in &queue {
+        seen[map.index(pos)?] = true;
+    }
+
+    while let Some(pos) = queue.pop_front() {
+        if is_bottom(map, pos) {
+            let path = unwind(map, &prev, pos);
+            let cost = path.len() as u64;
+            return Some(Route { path, cost });
+        }
+        for next in moves.iter().filter_map(|&step| map.normalize_pos(pos + step)) {
+            let i = map.index(next)?;
+            if !seen[i] && open(next) {
+                seen[i] = true;
+                prev[i] = Some(pos);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn dijkstra(map: &Map, moves: &[Vec2], costs: Costs) -> Option<Route> {
+    search(map, moves, costs, |_| 0)
+}
+
+// Dijkstra, guided by how many rows are left: every step down costs at least
+// `Costs::min`. Only maps that end at the bottom give a guide, on others the bottom
+// row can be just one step up.
+pub(crate) fn a_star(map: &Map, moves: &[Vec2], costs: Costs) -> Option<Route> {
+    let bounded = map.normalize_pos((0, -1).into()).is_none();
+    let max_down = moves.iter().map(|step| step.y).max().unwrap_or(0);
+    if !bounded || max_down <= 0 {
+        return search(map, moves, costs, |_| 0);
+    }
+    let bottom = map.size().y - 1;
+    search(map, moves, costs, |pos| {
+        let rows = bottom - pos.y;
+        // rounded up, the last step may overshoot
+        ((rows + max_down - 1) / max_down) as u64 * costs.min()
+    })
+}
+
+// A*, with a `heuristic` that never overestimates what is left to the bottom row.
+// Routes whose cost doesn't fit in a u64 are left out.
+fn search(
+    map: &Map,
+    moves: &[Vec2],
+    costs: Costs,
+    heuristic: impl Fn(Vec2) -> u64,
+) -> Option<Route> {
+    let cost = |pos| costs.of(map.get(pos));
+    let mut best = vec![u64::MAX; cells(map)];
+    let mut prev: Vec<Option<Vec2>> = vec![None; cells(map)];
+    // (estimated total, cost so far, index), the lowest estimate first, ties to the
+    // lowest index so that routes don't depend on the order of the heap
+    let mut heap = BinaryHeap::new();
+    for pos in top_row(map) {
+        let Some(c) = cost(pos) else { continue };
+        let Some(estimate) = c.checked_add(heuristic(pos)) else { continue };
+        let i = map.index(pos)?;
+        best[i] = c;
+        heap.push(Reverse((estimate, c, i)));
+    }
+
+    while let Some(Reverse((_, so_far, i))) = heap.pop() {
+        if so_far > best[i] {
+            // found a cheaper way here since
+            continue;
+        }
+        let pos = position(map, i);
+        if is_bottom(map, pos) {
+            return Some(Route {
+                path: unwind(map, &prev, pos),
+                cost: so_far,
+            });
+        }
+        for next in moves.iter().filter_map(|&step| map.normalize_pos(pos + step)) {
+            let Some(c) = cost(next).and_then(|c| so_far.checked_add(c)) else { continue };
+            let Some(estimate) = c.checked_add(heuristic(next)) else { continue };
+            let j = map.index(next)?;
+            if c < best[j] {
+                best[j] = c;
+                prev[j] = Some(pos);
+                heap.push(Reverse((estimate, c, j)));
+            }
+        }
+    }
+    None
+}
+
+fn cells(map: &Map) -> usize {
+    (map.size().x * map.size().y) as usize
+}
+
+fn top_row(map: &Map) -> impl Iterator<Item = Vec2> {
+    (0..map.size().x).map(|x| (x, 0).into())
+}
+
+fn is_bottom(map: &Map, pos: Vec2) -> bool {
+    pos.y == map.size().y - 1
+}
+
+// the inverse of `Map::index`, for normalized positions
+fn position(map: &Map, index: usize) -> Vec2 {
+    let width = map.size().x;
+    (index as i64 % width, index as i64 / width).into()
+}
+
+// the path that ends at `pos`, following `prev` back to the top row
+fn unwind(map: &Map, prev: &[Option<Vec2>], pos: Vec2) -> Vec<Vec2> {
+    let mut path = vec![pos];
+    while let Some(pos) = map.index(*path.last().unwrap()).and_then(|i| prev[i]) {
+        path.push(pos);
+    }
+    path.reverse();
+    path
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{a_star, bfs, dijkstra, Costs, Route};
+    use crate::{tests::EXAMPLE, Map, Topology, Vec2};
+
+    fn moves4() -> Vec<Vec2> {
+        Vec2::default().neighbors4().collect()
+    }
+
+    fn moves8() -> Vec<Vec2> {
+        Vec2::default().neighbors8().collect()
+    }
+
+    // every step is one of `moves`, and the route is as expensive as it says
+    fn check(map: &Map, moves: &[Vec2], costs: Costs, route: &Route) {
+        assert_eq!(route.path.first().unwrap().y, 0);
+        assert_eq!(route.path.last().unwrap().y, map.size().y - 1);
+        for w in route.path.windows(2) {
+            assert!(
+                moves.iter().any(|&step| map.normalize_pos(w[0] + step) == Some(w[1])),
+                "{:?} to {:?}", w[0], w[1]
+            );
+        }
+        let cost: u64 = route.path.iter().map(|&pos| costs.of(map.get(pos)).unwrap()).sum();
+        assert_eq!(cost, route.cost);
+    }
+
+    #[test]
+    fn test_bfs() {
+        let map = Map::parse("#.##\n#..#\n##.#\n##.#\n").unwrap();
+        let route = bfs(&map, &moves4()).unwrap();
+        assert_eq!(
+            route.path,
+            [(1, 0).into(), (1, 1).into(), (2, 1).into(), (2, 2).into(), (2, 3).into()]
+        );
+        assert_eq!(route.cost, 5);
+
+        let blocked = Map::parse("#.##\n#..#\n####\n#.##\n").unwrap();
+        assert_eq!(bfs(&blocked, &moves4()), None);
+        assert_eq!(bfs(&blocked, &moves8()), None, "no way past a full row of trees");
+
+        let diagonal = Map::parse("..#\n#.#\n.##\n").unwrap();
+        assert_eq!(bfs(&diagonal, &moves4()), None);
+        let route = bfs(&diagonal, &moves8()).unwrap();
+        assert_eq!(route.cost, 3);
+        check(&diagonal, &moves8(), Costs { tree: None }, &route);
+    }
+
+    #[test]
+    fn test_wraparound() {
+        let map = Map::parse(".##\n.#.\n##.\n").unwrap();
+        assert_eq!(
+            bfs(&map, &moves4()).unwrap().path,
+            [(0, 0).into(), (0, 1).into(), (2, 1).into(), (2, 2).into()],
+            "left across the edge"
+        );
+        assert_eq!(bfs(&map.with_topology(Topology::Bounded), &moves4()), None);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let input = ".....\n####.\n.....\n.####\n.....\n";
+        let map = Map::parse(input).unwrap();
+        let bounded = Map::parse(input).unwrap().with_topology(Topology::Bounded);
+        let cost = |map: &Map, tree| {
+            let costs = Costs { tree };
+            let route = dijkstra(map, &moves4(), costs).unwrap();
+            check(map, &moves4(), costs, &route);
+            let other = a_star(map, &moves4(), costs).unwrap();
+            check(map, &moves4(), costs, &other);
+            assert_eq!(route.cost, other.cost, "A* finds routes as cheap as Dijkstra's");
+            route.cost
+        };
+        assert_eq!(cost(&bounded, None), 9, "all the way around");
+        assert_eq!(cost(&bounded, None), bfs(&bounded, &moves4()).unwrap().cost);
+        assert_eq!(cost(&bounded, Some(10)), 9);
+        assert_eq!(cost(&bounded, Some(2)), 6, "cheaper through a tree");
+        assert_eq!(cost(&map, None), 6, "around, across the edge");
+        assert_eq!(cost(&map, Some(0)), 3, "straight through both walls, for free");
+        assert_eq!(cost(&bounded, Some(u64::MAX)), 9, "around, however costly the trees");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let costs = Costs { tree: Some(u64::MAX) };
+        for input in ["#\n", "#\n#\n", ".\n#\n", "#\n.\n"] {
+            let map = Map::parse(input).unwrap().with_topology(Topology::Bounded);
+            let expected = (input == "#\n").then_some(u64::MAX);
+            assert_eq!(dijkstra(&map, &moves4(), costs).map(|r| r.cost), expected, "{:?}", input);
+            assert_eq!(a_star(&map, &moves4(), costs).map(|r| r.cost), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_example() {
+        let downhill = vec![(0, 1).into(), (1, 1).into(), (-1, 1).into()];
+        for topology in Topology::ALL {
+            let map = Map::parse(EXAMPLE).unwrap().with_topology(topology);
+            for moves in [moves4(), moves8(), downhill.clone()] {
+                for tree in [None, Some(1), Some(3)] {
+                    let costs = Costs { tree };
+                    let (Some(dijkstra), Some(a_star)) =
+                        (dijkstra(&map, &moves, costs), a_star(&map, &moves, costs))
+                    else {
+                        panic!("no route on {:?} with {:?}", topology, moves);
+                    };
+                    check(&map, &moves, costs, &dijkstra);
+                    check(&map, &moves, costs, &a_star);
+                    assert_eq!(dijkstra.cost, a_star.cost, "{:?} {:?} {:?}", topology, moves, tree);
+                    if tree.is_none() {
+                        assert_eq!(Some(dijkstra.cost), bfs(&map, &moves).map(|r| r.cost));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+// Pictures of a map with the trips down its slopes drawn over it, as PPM, PNG or
+// SVG. Each slope gets its own color, and the trees it runs into are ringed in red.
+
+use std::io;
+
+use super::{generate_itinerary, grid::Grid, Map, Tile, Vec2};
+
+type Rgb = [u8; 3];
+
+const OPEN: Rgb = [0xf4, 0xf1, 0xe8];
+const TREE: Rgb = [0x2e, 0x6b, 0x30];
+const COLLISION: Rgb = [0xe0, 0x1b, 0x24];
+// cycled through, one per slope
+const SLOPES: [Rgb; 6] = [
+    [0x1c, 0x71, 0xd8],
+    [0xf5, 0xa6, 0x23],
+    [0x91, 0x41, 0xac],
+    [0x26, 0xa2, 0x69],
+    [0xe6, 0x61, 0x00],
+    [0x00, 0x9e, 0xb0],
+];
+
+pub(crate) struct Picture<'a> {
+    map: &'a Map,
+    slopes: Vec<Vec2>,
+    // how many copies of the map side by side, to follow trips around it
+    copies: i64,
+    // pixels per tile, for raster images
+    scale: usize,
+}
+
+// What a tile of the picture shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Tile(Tile),
+    // the index of the slope whose trip goes through it, the last one drawn wins
+    Visited { slope: usize, tile: Tile },
+}
+
+// an open tile, until the map is drawn in
+impl Default for Cell {
+    fn default() -> Self {
+        Cell::Tile(Tile::default())
+    }
+}
+
+impl<'a> Picture<'a> {
+    pub(crate) fn new(map: &'a Map) -> Self {
+        Self {
+            map,
+            slopes: Vec::new(),
+            copies: 1,
+            scale: 4,
+        }
+    }
+
+    pub(crate) fn with_slopes(self, slopes: &[Vec2]) -> Self {
+        Self { slopes: slopes.to_vec(), ..self }
+    }
+
+    pub(crate) fn with_copies(self, copies: i64) -> Self {
+        Self { copies: copies.max(1), ..self }
+    }
+
+    pub(crate) fn with_scale(self, scale: usize) -> Self {
+        Self { scale: scale.max(1), ..self }
+    }
+
+    // the size of the picture, in tiles
+    fn size(&self) -> Vec2 {
+        (self.map.size().x * self.copies, self.map.size().y).into()
+    }
+
+    // where a position of a trip is drawn: trips that leave the picture come back in
+    // at their place on the map
+    fn place(&self, pos: Vec2) -> Option<Vec2> {
+        let size = self.size();
+        if pos.x >= 0 && pos.x < size.x && pos.y >= 0 && pos.y < size.y {
+            Some(pos)
+        } else {
+            self.map.normalize_pos(pos)
+        }
+    }
+
+    // Every tile of the picture. The copies show whatever the map's topology puts
+    // next to it.
+    fn cells(&self) -> Grid<Cell> {
+        let size = self.size();
+        let mut cells = Grid::new(size);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = (x, y).into();
+                cells.set(pos, Cell::Tile(self.map.get(pos)));
+            }
+        }
+        for (slope, &delta) in self.slopes.iter().enumerate() {
+            for pos in generate_itinerary(self.map, delta).filter_map(|pos| self.place(pos)) {
+                cells.set(pos, Cell::Visited { slope, tile: self.map.get(pos) });
+            }
+        }
+        cells
+    }
+
+    // RGB pixels, row by row: `scale` pixels square per tile
+    fn pixels(&self) -> (usize, usize, Vec<u8>) {
+        let (cells, scale) = (self.cells(), self.scale);
+        let size = cells.size();
+        let (width, height) = (size.x as usize * scale, size.y as usize * scale);
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for py in 0..height {
+            for px in 0..width {
+                let pos = ((px / scale) as i64, (py / scale) as i64).into();
+                let cell = *cells.get(pos).unwrap();
+                // the ring around a collision is one pixel wide, or a quarter tile
+                let ring = (scale / 4).max(1);
+                let (x, y) = (px % scale, py % scale);
+                let on_ring = x < ring || y < ring || x >= scale - ring || y >= scale - ring;
+                pixels.extend_from_slice(&match cell {
+                    Cell::Tile(Tile::Open) => OPEN,
+                    Cell::Tile(Tile::Tree) => TREE,
+                    Cell::Visited { tile: Tile::Tree, .. } if on_ring || scale < 3 => COLLISION,
+                    Cell::Visited { slope, .. } => SLOPES[slope % SLOPES.len()],
+                });
+            }
+        }
+        (width, height, pixels)
+    }
+
+    // binary PPM (P6)
+    pub(crate) fn write_ppm(&self, mut w: impl io::Write) -> anyhow::Result<()> {
+        let (width, height, pixels) = self.pixels();
+        write!(w, "P6\n{} {}\n255\n", width, height)?;
+        w.write_all(&pixels)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn write_png(&self, w: impl io::Write) -> anyhow::Result<()> {
+        let (width, height, pixels) = self.pixels();
+        let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(())
+    }
+
+    // one unit per tile, the map as squares, and each trip as a line through the
+    // centers of its tiles, breaking where it comes back in elsewhere
+    pub(crate) fn write_svg(&self, mut w: impl io::Write) -> anyhow::Result<()> {
+        let size = self.size();
+        writeln!(
+            w,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" width="{}" height="{}">"#,
+            size.x, size.y, size.x as usize * self.scale, size.y as usize * self.scale
+        )?;
+        writeln!(w, r#"<rect width="{}" height="{}" fill="{}"/>"#, size.x, size.y, hex(OPEN))?;
+        writeln!(w, r#"<g fill="{}">"#, hex(TREE))?;
+        for y in 0..size.y {
+            for x in 0..size.x {
+                if self.map.get((x, y).into()) == Tile::Tree {
+                    writeln!(w, r#"<rect x="{}" y="{}" width="1" height="1"/>"#, x, y)?;
+                }
+            }
+        }
+        writeln!(w, "</g>")?;
+
+        for (slope, &delta) in self.slopes.iter().enumerate() {
+            let color = hex(SLOPES[slope % SLOPES.len()]);
+            writeln!(
+                w,
+                r#"<g class="slope" data-right="{}" data-down="{}" stroke="{}" stroke-width="0.3" fill="none" stroke-linecap="round">"#,
+                delta.x, delta.y, color
+            )?;
+            let mut line: Vec<Vec2> = Vec::new();
+            let mut collisions = Vec::new();
+            for pos in generate_itinerary(self.map, delta).filter_map(|pos| self.place(pos)) {
+                if line.last().is_some_and(|&last| last + delta != pos) {
+                    write_polyline(&mut w, &line)?;
+                    line.clear();
+                }
+                line.push(pos);
+                if self.map.get(pos) == Tile::Tree {
+                    collisions.push(pos);
+                }
+            }
+            write_polyline(&mut w, &line)?;
+            for pos in collisions {
+                writeln!(
+                    w,
+                    r#"<circle cx="{}.5" cy="{}.5" r="0.45" stroke="{}" stroke-width="0.1"/>"#,
+                    pos.x, pos.y, hex(COLLISION)
+                )?;
+            }
+            writeln!(w, "</g>")?;
+        }
+        writeln!(w, "</svg>")?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+// a single tile is a dot
+fn write_polyline(w: &mut impl io::Write, line: &[Vec2]) -> io::Result<()> {
+    let points: Vec<String> = match line {
+        [] => return Ok(()),
+        &[pos] => vec![format!("{}.5,{}.5", pos.x, pos.y); 2],
+        line => line.iter().map(|pos| format!("{}.5,{}.5", pos.x, pos.y)).collect(),
+    };
+    writeln!(w, r#"<polyline points="{}"/>"#, points.join(" "))
+}
+
+fn hex([r, g, b]: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Cell, Picture, COLLISION, SLOPES, TREE};
+    use crate::{Map, Tile};
+
+    #[test]
+    fn test_cells() {
+        let map = Map::parse("..#\n#..\n.#.\n").unwrap();
+        let picture = Picture::new(&map).with_slopes(&[(1, 1).into(), (2, 1).into()]);
+        let visited = |slope, tile| Cell::Visited { slope, tile };
+        let cells = picture.cells();
+        let rows: Vec<Vec<Cell>> = (0..3)
+            .map(|y| (0..3).map(|x| *cells.get((x, y).into()).unwrap()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                [visited(1, Tile::Open), Cell::Tile(Tile::Open), Cell::Tile(Tile::Tree)],
+                [Cell::Tile(Tile::Tree), visited(0, Tile::Open), visited(1, Tile::Open)],
+                [Cell::Tile(Tile::Open), visited(1, Tile::Tree), visited(0, Tile::Open)],
+            ],
+            "(2, 1) wraps around to the second row, then lands on a tree"
+        );
+
+        let picture = picture.with_copies(2);
+        let cells = picture.cells();
+        let cell = |x, y| *cells.get((x, y).into()).unwrap();
+        assert_eq!(cells.size(), (6, 3).into());
+        assert_eq!(cell(2, 0), Cell::Tile(Tile::Tree));
+        assert_eq!(cell(5, 0), Cell::Tile(Tile::Tree), "the copy to the right");
+        assert_eq!(cell(2, 1), visited(1, Tile::Open), "(2, 1) stays on the copy");
+        assert_eq!(cell(4, 2), visited(1, Tile::Tree));
+    }
+
+    #[test]
+    fn test_ppm() {
+        let map = Map::parse(".#\n#.\n").unwrap();
+        let mut out = Vec::new();
+        Picture::new(&map)
+            .with_slopes(&[(1, 1).into()])
+            .with_scale(1)
+            .write_ppm(&mut out)
+            .unwrap();
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(out[header.len()..], [SLOPES[0], TREE, TREE, SLOPES[0]].concat());
+
+        let map = Map::parse("#\n").unwrap();
+        let mut out = Vec::new();
+        Picture::new(&map).with_slopes(&[(0, 1).into()]).with_scale(4).write_ppm(&mut out).unwrap();
+        let pixels = &out[b"P6\n4 4\n255\n".len()..];
+        assert_eq!(pixels.len(), 4 * 4 * 3);
+        assert_eq!(pixels[..3], COLLISION, "the ring around a collision");
+        assert_eq!(pixels[(4 + 1) * 3..(4 + 2) * 3], SLOPES[0], "the slope's color inside");
+    }
+
+    #[test]
+    fn test_png() {
+        let map = Map::parse(crate::tests::EXAMPLE).unwrap();
+        let mut out = Vec::new();
+        Picture::new(&map).with_slopes(&[(3, 1).into()]).write_png(&mut out).unwrap();
+        assert_eq!(out[..8], *b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_svg() {
+        let map = Map::parse("..#\n#..\n.#.\n").unwrap();
+        let mut out = Vec::new();
+        Picture::new(&map)
+            .with_slopes(&[(1, 1).into(), (2, 1).into()])
+            .write_svg(&mut out)
+            .unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 3 3""#));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect x=").count(), 3, "one square per tree");
+        assert!(svg.contains(r#"<polyline points="0.5,0.5 1.5,1.5 2.5,2.5"/>"#));
+        assert!(
+            svg.contains(r#"<polyline points="0.5,0.5"#) && svg.contains(r#"<polyline points="1.5,2.5 1.5,2.5"/>"#),
+            "the (2, 1) trip breaks where it wraps around:\n{}", svg
+        );
+        assert_eq!(svg.matches("<circle").count(), 1, "a single collision");
+    }
+}
@@ -0,0 +1,219 @@
+// Counting trees down a slope without taking every step. On a map that repeats to
+// the left and right, a slope `(dx, dy)` is back in the same column every `period`
+// steps, `period` being `width / gcd(dx, width)`. Step `n` is in the column of step
+// `n % period`, on a row that is `n % period * dy` modulo `period * dy`: the trees
+// along the slope are the trees of `period` columns, each on the rows of a single
+// residue.
+
+use super::{bits::BitGrid, vec2::gcd, Vec2};
+
+// The trees of a map, by column and by row modulo `modulus`
+pub(crate) struct Residues {
+    modulus: i64,
+    width: i64,
+    // at `residue * width + column`
+    trees: Vec<u32>,
+}
+
+// What `Residues` a slope down a map of `size` needs. None when there is no point
+// to them: a slope that doesn't go down never gets to repeat on enough rows, and
+// when no two rows share a residue, taking every step is quicker.
+pub(crate) fn modulus(delta: Vec2, size: Vec2) -> Option<i64> {
+    if delta.y <= 0 || size.x <= 0 {
+        return None;
+    }
+    let modulus = size.x / gcd(delta.x, size.x) * delta.y;
+    (modulus < size.y).then_some(modulus)
+}
+
+// Whether `Residues` for `modulus` on `grid` with `trees` trees are worth it for
+// slopes that would take `steps` steps otherwise. Building them goes over every row
+// and every tree, and they shouldn't take more room than the grid does.
+pub(crate) fn pays_off(grid: &BitGrid, trees: usize, modulus: i64, steps: usize) -> bool {
+    let size = grid.size();
+    let bits = (modulus * size.x) as usize * u32::BITS as usize;
+    steps > size.y as usize + trees && bits <= grid.bits()
+}
+
+// The trees along `generate_itinerary` on a horizontal cylinder, for a slope that
+// goes down, without any residues: the rows of the slope's `period` columns, counted
+// a word of each column at a time.
+pub(crate) fn trees_by_columns(grid: &BitGrid, delta: Vec2) -> usize {
+    let size = grid.size();
+    if size.x <= 0 {
+        return 0;
+    }
+    let period = size.x / gcd(delta.x, size.x);
+    (0..period)
+        .take_while(|n| n * delta.y < size.y)
+        .map(|n| grid.count_column((n * delta.x).rem_euclid(size.x), n * delta.y, period * delta.y))
+        .sum()
+}
+
+impl Residues {
+    // a pass over every row, a set bit at a time
+    pub(crate) fn new(grid: &BitGrid, modulus: i64) -> Self {
+        let width = grid.size().x;
+        let mut trees = vec![0; (modulus * width) as usize];
+        for y in 0..grid.size().y {
+            let row = y % modulus * width;
+            for x in grid.row_ones(y) {
+                trees[(row + x) as usize] += 1;
+            }
+        }
+        Self { modulus, width, trees }
+    }
+
+    pub(crate) fn modulus(&self) -> i64 {
+        self.modulus
+    }
+
+    // The trees along `generate_itinerary` on a horizontal cylinder, in as many
+    // steps as the slope's period. `delta` has to be a slope that `modulus` gave
+    // these residues for.
+    pub(crate) fn trees_along(&self, delta: Vec2) -> usize {
+        let period = self.modulus / delta.y;
+        (0..period)
+            .map(|n| {
+                let x = (n * delta.x).rem_euclid(self.width);
+                self.trees[(n * delta.y * self.width + x) as usize] as usize
+            })
+            .sum()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{modulus, pays_off, trees_by_columns, BitGrid, Residues};
+    use crate::{
+        generate_itinerary,
+        tests::{forest, EXAMPLE},
+        Map, Tile, Topology, Vec2,
+    };
+
+    // taking every step
+    fn walk(map: &Map, delta: Vec2) -> usize {
+        generate_itinerary(map, delta)
+            .filter(|&pos| map.get(pos) == Tile::Tree)
+            .count()
+    }
+
+    #[test]
+    fn test_modulus() {
+        let size = (12, 100).into();
+        assert_eq!(modulus((3, 1).into(), size), Some(4));
+        assert_eq!(modulus((5, 1).into(), size), Some(12));
+        assert_eq!(modulus((-8, 2).into(), size), Some(6));
+        assert_eq!(modulus((0, 7).into(), size), Some(7), "straight down");
+        assert_eq!(modulus((5, 9).into(), size), None, "every row a residue of its own");
+        assert_eq!(modulus((1, 0).into(), size), None);
+        assert_eq!(modulus((1, -1).into(), size), None);
+    }
+
+    #[test]
+    fn test_pays_off() {
+        let grid = BitGrid::new((31, 10_000).into());
+        assert!(pays_off(&grid, 80_000, 31, 100_000));
+        assert!(!pays_off(&grid, 80_000, 31, 90_000), "not as many steps as rows and trees");
+        assert!(pays_off(&grid, 0, 500, 1 << 40), "more than a bit per tile, but both copies");
+        assert!(!pays_off(&grid, 0, 9_000, 1 << 40), "more room than the map's bits");
+    }
+
+    #[test]
+    fn test_trees_along() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let residues = Residues::new(&map.grid, 11);
+        assert_eq!(residues.trees_along((3, 1).into()), 7);
+        assert_eq!(residues.trees_along((14, 1).into()), 7);
+
+        for size in [(1, 50), (12, 100), (31, 323), (70, 500)] {
+            let map = forest(size.into(), 0x9e37_79b9_7f4a_7c15);
+            for dx in -80..=80 {
+                for dy in 1..=6 {
+                    let delta = (dx, dy).into();
+                    if let Some(m) = modulus(delta, map.size()) {
+                        let residues = Residues::new(&map.grid, m);
+                        assert_eq!(
+                            residues.trees_along(delta),
+                            walk(&map, delta),
+                            "{:?} {:?}", size, delta
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_trees_by_columns() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        assert_eq!(trees_by_columns(&map.grid, (3, 1).into()), 7);
+        assert_eq!(trees_by_columns(&map.grid, (1, 2).into()), 2);
+        let empty = Map::new((0, 5).into());
+        assert_eq!(trees_by_columns(&empty.grid, (0, 1).into()), 0, "no columns at all");
+        assert_eq!(empty.count_along((0, 1).into(), Tile::Tree), 0);
+
+        // steps within a word of a column, across words, and past a whole word
+        for size in [(1, 300), (12, 100), (31, 323), (70, 500)] {
+            let map = forest(size.into(), 0x9e37_79b9_7f4a_7c15);
+            for dx in -80..=80 {
+                for dy in [1, 2, 5, 63, 64, 65, 200] {
+                    let delta = (dx, dy).into();
+                    assert_eq!(
+                        trees_by_columns(&map.grid, delta),
+                        walk(&map, delta),
+                        "{:?} {:?}", size, delta
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_along() {
+        for topology in Topology::ALL {
+            let map = forest((12, 200).into(), 0x2545_f491_4f6c_dd1d).with_topology(topology);
+            for dx in -30..=30 {
+                for dy in -3..=30 {
+                    let delta = (dx, dy).into();
+                    // every other slope with its residues, if it can have any
+                    if let Some(m) = map.residue_modulus(delta).filter(|_| (dx + dy) % 2 == 0) {
+                        map.precompute(m);
+                    }
+                    assert_eq!(
+                        map.count_along(delta, Tile::Tree),
+                        walk(&map, delta),
+                        "{:?} {:?}", topology, delta
+                    );
+                    assert_eq!(
+                        map.count_along(delta, Tile::Open),
+                        generate_itinerary(&map, delta).len() - walk(&map, delta)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_set() {
+        let mut map = forest((5, 40).into(), 7);
+        map.precompute(5);
+        let before = map.count_along((2, 1).into(), Tile::Tree);
+        let pos = (0, 0).into();
+        map.set(pos, if map.get(pos) == Tile::Tree { Tile::Open } else { Tile::Tree });
+        assert_ne!(map.count_along((2, 1).into(), Tile::Tree), before, "no stale residues");
+        assert_eq!(map.count_along((2, 1).into(), Tile::Tree), walk(&map, (2, 1).into()));
+        assert!(map.residues.borrow().is_none());
+    }
+
+    #[test]
+    fn test_tall_map() {
+        // half a million rows, but only three columns to come back to
+        let map = forest((3, 500_000).into(), 42);
+        for delta in [(1, 1), (3, 1), (2, 5), (-1, 3)].map(Vec2::from) {
+            map.precompute(map.residue_modulus(delta).unwrap());
+            assert_eq!(map.count_along(delta, Tile::Tree), walk(&map, delta));
+        }
+    }
+}
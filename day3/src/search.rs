@@ -0,0 +1,177 @@
+// Searching for the best (or worst) slope down a map: every slope in a range is
+// tried, and slopes that visit the very same tiles are only counted once.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::RangeInclusive,
+};
+
+use super::{generate_itinerary, residue, Map, Tile, Vec2};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Order {
+    Fewest,
+    Most,
+}
+
+impl Order {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "fewest" => Some(Order::Fewest),
+            "most" => Some(Order::Most),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) struct SlopeSearch<'a> {
+    map: &'a Map,
+    // trees along each slope, by `Topology::canonical` slope
+    trees: HashMap<Vec2, usize>,
+}
+
+impl<'a> SlopeSearch<'a> {
+    pub(crate) fn new(map: &'a Map) -> Self {
+        Self {
+            map,
+            trees: HashMap::new(),
+        }
+    }
+
+    // the trees along `generate_itinerary`, counted once per distinct trip
+    pub(crate) fn trees(&mut self, delta: Vec2) -> usize {
+        let map = self.map;
+        *self.trees
+            .entry(map.topology.canonical(delta, map.size()))
+            .or_insert_with(|| map.count_along(delta, Tile::Tree))
+    }
+
+    // Counts the trees along `slopes` that share residues together, for the residues
+    // that take fewer steps to work out than walking down all those slopes does
+    fn count_by_residues(&mut self, slopes: &[Vec2]) {
+        let map = self.map;
+        // the slopes not counted yet, once per trip, by modulus
+        let mut shared: BTreeMap<i64, HashMap<Vec2, Vec2>> = BTreeMap::new();
+        for &delta in slopes {
+            let canonical = map.topology.canonical(delta, map.size());
+            if self.trees.contains_key(&canonical) {
+                continue;
+            }
+            if let Some(modulus) = map.residue_modulus(delta) {
+                shared.entry(modulus).or_default().insert(canonical, delta);
+            }
+        }
+        let trees = map.count(Tile::Tree);
+        for (modulus, slopes) in shared {
+            let steps = slopes.values().map(|&delta| generate_itinerary(map, delta).len()).sum();
+            if residue::pays_off(&map.grid, trees, modulus, steps) {
+                map.precompute(modulus);
+                for delta in slopes.into_values() {
+                    self.trees(delta);
+                }
+            }
+        }
+    }
+
+    // Every slope with `dx` and `dy` in range, standing still excepted, from the
+    // fewest trees to the most or the other way around. Ties go to the slope that
+    // comes first going down, then right.
+    pub(crate) fn rank(
+        &mut self,
+        dx: RangeInclusive<i64>,
+        dy: RangeInclusive<i64>,
+        order: Order,
+    ) -> Vec<(Vec2, usize)> {
+        let slopes: Vec<Vec2> = dy
+            .flat_map(|y| dx.clone().map(move |x| Vec2::from((x, y))))
+            .filter(|&delta| delta != Vec2::default())
+            .collect();
+        self.count_by_residues(&slopes);
+        let mut ranked: Vec<(Vec2, usize)> = slopes
+            .into_iter()
+            .map(|delta| (delta, self.trees(delta)))
+            .collect();
+        // stable, so ties stay in scanning order
+        match order {
+            Order::Fewest => ranked.sort_by_key(|&(_, trees)| trees),
+            Order::Most => ranked.sort_by_key(|&(_, trees)| std::cmp::Reverse(trees)),
+        }
+        ranked
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_itinerary, Order, SlopeSearch};
+    use crate::{
+        tests::{forest, EXAMPLE},
+        Map, Tile, Topology,
+    };
+
+    #[test]
+    fn test_trees() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let mut search = SlopeSearch::new(&map);
+        assert_eq!(search.trees((3, 1).into()), 7);
+        assert_eq!(search.trees((14, 1).into()), 7, "one more time around the map");
+        assert_eq!(search.trees.len(), 1, "and the same trip");
+        assert_eq!(search.trees((3, 2).into()), map.count_along((3, 2).into(), Tile::Tree));
+        assert_eq!(search.trees.len(), 2);
+    }
+
+    #[test]
+    fn test_rank() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let mut search = SlopeSearch::new(&map);
+        let ranked = search.rank(0..=7, 1..=2, Order::Most);
+        assert_eq!(ranked.len(), 16);
+        assert_eq!(ranked[0], ((3, 1).into(), 7));
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1), "most trees first");
+
+        let ranked = search.rank(0..=7, 0..=2, Order::Fewest);
+        assert_eq!(ranked.len(), 23, "standing still is no slope");
+        assert!(ranked.windows(2).all(|w| w[0].1 <= w[1].1), "fewest trees first");
+        for (delta, trees) in ranked {
+            assert_eq!(trees, map.count_along(delta, Tile::Tree), "{:?}", delta);
+        }
+    }
+
+    #[test]
+    fn test_rank_topologies() {
+        // memoized slopes have to be the same trip under every topology
+        for topology in Topology::ALL {
+            let map = Map::parse(EXAMPLE).unwrap().with_topology(topology);
+            let mut search = SlopeSearch::new(&map);
+            for (delta, trees) in search.rank(-25..=25, -25..=25, Order::Fewest) {
+                assert_eq!(
+                    trees,
+                    map.count_along(delta, Tile::Tree),
+                    "{:?} {:?}", topology, delta
+                );
+            }
+            assert!(search.trees.len() < 50 * 50);
+        }
+    }
+
+    #[test]
+    fn test_rank_tall_map() {
+        let map = forest((31, 20_000).into(), 3);
+        let mut search = SlopeSearch::new(&map);
+        let ranked = search.rank(0..=30, 1..=200, Order::Most);
+        assert_eq!(ranked.len(), 31 * 200);
+        for &(delta, trees) in ranked.iter().step_by(97) {
+            let walked = generate_itinerary(&map, delta)
+                .filter(|&pos| map.get(pos) == Tile::Tree)
+                .count();
+            assert_eq!(trees, walked, "{:?}", delta);
+        }
+
+        // only the residues of the steepest slopes are worth it, and only the last of
+        // them are kept, no bigger than the map
+        let residues = map.residues.borrow();
+        let modulus = residues.as_ref().map(|residues| residues.modulus()).unwrap();
+        assert!(modulus <= 31 * 3, "{}", modulus);
+        assert!((modulus * 31 * 32) as usize <= map.grid.bits());
+    }
+}
@@ -0,0 +1,234 @@
+use super::{vec2::gcd, Vec2};
+
+// What lies past the edges of a map
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) enum Topology {
+    // nothing, in any direction
+    Bounded,
+    // the map repeats to the left and right, the puzzle's forest
+    #[default]
+    HorizontalCylinder,
+    // the map repeats above and below
+    VerticalCylinder,
+    // the map repeats in every direction
+    Torus,
+    // every edge is a mirror: past it, the map repeats flipped
+    Mirrored,
+}
+
+// What lies past the two edges of one axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edge {
+    Bounded,
+    Wrapped,
+    Mirrored,
+}
+
+// How a straight trip along one axis ends, counted in positions visited
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Trip {
+    // off the map after this many
+    Leaves(usize),
+    // back where it started, facing the same way, after this many
+    Repeats(usize),
+}
+
+impl Topology {
+    #[cfg(test)]
+    pub(crate) const ALL: [Topology; 5] = [
+        Topology::Bounded,
+        Topology::HorizontalCylinder,
+        Topology::VerticalCylinder,
+        Topology::Torus,
+        Topology::Mirrored,
+    ];
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "bounded" => Some(Topology::Bounded),
+            "horizontal-cylinder" => Some(Topology::HorizontalCylinder),
+            "vertical-cylinder" => Some(Topology::VerticalCylinder),
+            "torus" => Some(Topology::Torus),
+            "mirrored" => Some(Topology::Mirrored),
+            _ => None,
+        }
+    }
+
+    // the edges of the x and y axes
+    fn edges(self) -> [Edge; 2] {
+        match self {
+            Topology::Bounded => [Edge::Bounded, Edge::Bounded],
+            Topology::HorizontalCylinder => [Edge::Wrapped, Edge::Bounded],
+            Topology::VerticalCylinder => [Edge::Bounded, Edge::Wrapped],
+            Topology::Torus => [Edge::Wrapped, Edge::Wrapped],
+            Topology::Mirrored => [Edge::Mirrored, Edge::Mirrored],
+        }
+    }
+
+    // the position within a map of `size` that `pos` stands for, None if there is
+    // none
+    pub(crate) fn normalize(self, pos: Vec2, size: Vec2) -> Option<Vec2> {
+        let [x, y] = self.edges();
+        Some((x.normalize(pos.x, size.x)?, y.normalize(pos.y, size.y)?).into())
+    }
+
+    // The smallest slope that makes the same trip as `delta` does: the same tiles,
+    // in the same order, on a map of `size`
+    pub(crate) fn canonical(self, delta: Vec2, size: Vec2) -> Vec2 {
+        let [x, y] = self.edges();
+        (x.canonical(delta.x, size.x), y.canonical(delta.y, size.y)).into()
+    }
+
+    // How many positions a straight trip from the top left corner of a map of `size`
+    // visits, `delta` at a time, before it leaves the map. On a map without an end in
+    // that direction, the trip stops before it gets back to the same place facing the
+    // same way: from there on it would only repeat itself.
+    pub(crate) fn trip_len(self, delta: Vec2, size: Vec2) -> usize {
+        if size.x <= 0 || size.y <= 0 {
+            return 0;
+        }
+        let [x, y] = self.edges();
+        match (x.trip(delta.x, size.x), y.trip(delta.y, size.y)) {
+            (Trip::Leaves(x), Trip::Leaves(y)) => x.min(y),
+            (Trip::Leaves(n), Trip::Repeats(_)) | (Trip::Repeats(_), Trip::Leaves(n)) => n,
+            // both axes have to be back at the same time
+            (Trip::Repeats(x), Trip::Repeats(y)) => x / gcd(x as i64, y as i64) as usize * y,
+        }
+    }
+}
+
+impl Edge {
+    fn normalize(self, n: i64, len: i64) -> Option<i64> {
+        match self {
+            Edge::Bounded => (0..len).contains(&n).then_some(n),
+            Edge::Wrapped => Some(n.rem_euclid(len)),
+            Edge::Mirrored => {
+                // every other copy of the map is flipped
+                let n = n.rem_euclid(2 * len);
+                Some(if n < len { n } else { 2 * len - 1 - n })
+            }
+        }
+    }
+
+    fn canonical(self, d: i64, len: i64) -> i64 {
+        match self {
+            // any step off the map is as good as another
+            Edge::Bounded => d.clamp(-1, len),
+            Edge::Wrapped => d.rem_euclid(len),
+            Edge::Mirrored => d.rem_euclid(2 * len),
+        }
+    }
+
+    // a trip from 0, `d` at a time, on an axis `len` long
+    fn trip(self, d: i64, len: i64) -> Trip {
+        // the period of the copies of the map, flipped ones included
+        let period = match self {
+            Edge::Bounded if d > 0 => return Trip::Leaves(((len - 1) / d + 1) as usize),
+            Edge::Bounded if d < 0 => return Trip::Leaves(1),
+            // standing still
+            Edge::Bounded => return Trip::Repeats(1),
+            Edge::Wrapped => len,
+            Edge::Mirrored => 2 * len,
+        };
+        Trip::Repeats((period / gcd(d, period)) as usize)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Topology, Vec2};
+
+    #[test]
+    fn test_normalize() {
+        let size = (3, 2).into();
+        let normalize = |topology: Topology, x, y| topology.normalize((x, y).into(), size);
+        for topology in Topology::ALL {
+            assert_eq!(normalize(topology, 2, 1), Some((2, 1).into()), "{:?}", topology);
+        }
+
+        assert_eq!(normalize(Topology::Bounded, 3, 0), None);
+        assert_eq!(normalize(Topology::Bounded, 0, -1), None);
+
+        assert_eq!(normalize(Topology::HorizontalCylinder, 4, 1), Some((1, 1).into()));
+        assert_eq!(normalize(Topology::HorizontalCylinder, -1, 0), Some((2, 0).into()));
+        assert_eq!(normalize(Topology::HorizontalCylinder, 0, 2), None);
+
+        assert_eq!(normalize(Topology::VerticalCylinder, 0, 3), Some((0, 1).into()));
+        assert_eq!(normalize(Topology::VerticalCylinder, 0, -2), Some((0, 0).into()));
+        assert_eq!(normalize(Topology::VerticalCylinder, 3, 0), None);
+
+        assert_eq!(normalize(Topology::Torus, -4, 5), Some((2, 1).into()));
+
+        // 0 1 2 | 2 1 0 | 0 1 2 ...
+        assert_eq!(normalize(Topology::Mirrored, 3, 0), Some((2, 0).into()));
+        assert_eq!(normalize(Topology::Mirrored, 5, 0), Some((0, 0).into()));
+        assert_eq!(normalize(Topology::Mirrored, 6, 0), Some((0, 0).into()));
+        assert_eq!(normalize(Topology::Mirrored, -1, 0), Some((0, 0).into()));
+        assert_eq!(normalize(Topology::Mirrored, -4, 0), Some((2, 0).into()));
+        assert_eq!(normalize(Topology::Mirrored, 0, 2), Some((0, 1).into()));
+    }
+
+    #[test]
+    fn test_trip_len() {
+        let size = (3, 2).into();
+        let len = |topology: Topology, x, y| topology.trip_len((x, y).into(), size);
+        assert_eq!(len(Topology::Bounded, 1, 1), 2);
+        assert_eq!(len(Topology::Bounded, 1, 0), 3);
+        assert_eq!(len(Topology::Bounded, -1, 0), 1);
+        assert_eq!(len(Topology::Bounded, 0, 0), 1, "standing still");
+        assert_eq!(len(Topology::HorizontalCylinder, 2, 0), 3, "once around");
+        assert_eq!(len(Topology::HorizontalCylinder, 3, 0), 1);
+        assert_eq!(len(Topology::HorizontalCylinder, 5, 1), 2, "no way back up");
+        assert_eq!(len(Topology::VerticalCylinder, 0, 1), 2);
+        assert_eq!(len(Topology::VerticalCylinder, 1, 1), 3);
+        assert_eq!(len(Topology::Torus, 1, 1), 6);
+        assert_eq!(len(Topology::Torus, 3, 2), 1);
+        assert_eq!(len(Topology::Mirrored, 1, 0), 6, "there and back again");
+        assert_eq!(len(Topology::Mirrored, 3, 1), 4);
+        assert_eq!(Topology::Torus.trip_len((1, 1).into(), (0, 0).into()), 0, "no map");
+    }
+
+    #[test]
+    fn test_trip_len_stepwise() {
+        // walk until the position is gone or the trip is back at its start, facing
+        // the same way: with mirrors, that takes the unfolded position back to 0
+        let walk = |topology: Topology, delta: Vec2, size: Vec2| {
+            let period = |len| match topology {
+                Topology::Mirrored => 2 * len,
+                _ => len,
+            };
+            let [wraps_x, wraps_y] = match topology {
+                Topology::Bounded => [false, false],
+                Topology::HorizontalCylinder => [true, false],
+                Topology::VerticalCylinder => [false, true],
+                Topology::Torus | Topology::Mirrored => [true, true],
+            };
+            let repeats = |d: i64, len, wraps| if wraps { d % period(len) == 0 } else { d == 0 };
+            let mut pos: Vec2 = (0, 0).into();
+            let mut n = 0;
+            while topology.normalize(pos, size).is_some() {
+                n += 1;
+                pos += delta;
+                if repeats(pos.x, size.x, wraps_x) && repeats(pos.y, size.y, wraps_y) {
+                    break;
+                }
+            }
+            n
+        };
+        for topology in Topology::ALL {
+            for size in [(1, 1), (3, 2), (4, 6), (7, 5)] {
+                for dx in -5..=5 {
+                    for dy in -5..=5 {
+                        let (delta, size) = ((dx, dy).into(), size.into());
+                        assert_eq!(
+                            topology.trip_len(delta, size),
+                            walk(topology, delta, size),
+                            "{:?} {:?} {:?}", topology, delta, size
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+// x grows to the right and y downwards, like rows and columns of a map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub(crate) struct Vec2 {
+    pub(crate) x: i64,
+    pub(crate) y: i64,
+}
+
+// the 4 orthogonal steps, clockwise from the right
+const ORTHOGONAL: [Vec2; 4] = [
+    Vec2 { x: 1, y: 0 },
+    Vec2 { x: 0, y: 1 },
+    Vec2 { x: -1, y: 0 },
+    Vec2 { x: 0, y: -1 },
+];
+
+// the 4 diagonal steps, clockwise from the bottom right
+const DIAGONAL: [Vec2; 4] = [
+    Vec2 { x: 1, y: 1 },
+    Vec2 { x: -1, y: 1 },
+    Vec2 { x: -1, y: -1 },
+    Vec2 { x: 1, y: -1 },
+];
+
+// the greatest common divisor, always positive, and 0 only if both are 0
+pub(crate) fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// the distances, `rotate` and `direction` are tools that no puzzle has needed yet
+impl Vec2 {
+    #[allow(dead_code)]
+    pub(crate) fn manhattan(self, other: Vec2) -> i64 {
+        let d = other - self;
+        d.x.abs() + d.y.abs()
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn chebyshev(self, other: Vec2) -> i64 {
+        let d = other - self;
+        d.x.abs().max(d.y.abs())
+    }
+
+    // turns by `quarter_turns` times 90°, clockwise as drawn (y goes down), and
+    // counter-clockwise for negative turns
+    #[allow(dead_code)]
+    pub(crate) fn rotate(self, quarter_turns: i64) -> Vec2 {
+        match quarter_turns.rem_euclid(4) {
+            0 => self,
+            1 => (-self.y, self.x).into(),
+            2 => -self,
+            _ => (self.y, -self.x).into(),
+        }
+    }
+
+    // the smallest step in the same direction, e.g. (2, 4) becomes (1, 2): every
+    // whole position on the line from 0 to `self` is a multiple of it
+    #[allow(dead_code)]
+    pub(crate) fn direction(self) -> Vec2 {
+        match gcd(self.x, self.y) {
+            0 => self,
+            d => self / d,
+        }
+    }
+
+    pub(crate) fn neighbors4(self) -> impl Iterator<Item = Vec2> {
+        ORTHOGONAL.into_iter().map(move |step| self + step)
+    }
+
+    // the orthogonal neighbors first, then the diagonal ones
+    pub(crate) fn neighbors8(self) -> impl Iterator<Item = Vec2> {
+        ORTHOGONAL.into_iter().chain(DIAGONAL).map(move |step| self + step)
+    }
+}
+
+// Build vec2 from a tuple
+impl From<(i64, i64)> for Vec2 {
+    fn from((x, y): (i64, i64)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        (self.x + rhs.x, self.y + rhs.y).into()
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        (self.x - rhs.x, self.y - rhs.y).into()
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        (-self.x, -self.y).into()
+    }
+}
+
+impl Mul<i64> for Vec2 {
+    type Output = Self;
+
+    fn mul(self, rhs: i64) -> Self {
+        (self.x * rhs, self.y * rhs).into()
+    }
+}
+
+// like integer division, rounds towards zero
+impl Div<i64> for Vec2 {
+    type Output = Self;
+
+    fn div(self, rhs: i64) -> Self {
+        (self.x / rhs, self.y / rhs).into()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{gcd, Vec2};
+
+    fn v(x: i64, y: i64) -> Vec2 {
+        (x, y).into()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(v(1, 2) + v(3, -4), v(4, -2));
+        assert_eq!(v(1, 2) - v(3, -4), v(-2, 6));
+        assert_eq!(-v(1, -2), v(-1, 2));
+        assert_eq!(v(1, -2) * 3, v(3, -6));
+        assert_eq!(v(7, -7) / 2, v(3, -3), "rounds towards zero");
+
+        let mut p = v(1, 1);
+        p += v(2, 3);
+        assert_eq!(p, v(3, 4));
+        p -= v(3, 1);
+        assert_eq!(p, v(0, 3));
+    }
+
+    #[test]
+    fn test_distances() {
+        assert_eq!(v(1, 1).manhattan(v(4, -3)), 7);
+        assert_eq!(v(4, -3).manhattan(v(1, 1)), 7);
+        assert_eq!(v(1, 1).chebyshev(v(4, -3)), 4);
+        assert_eq!(v(2, 2).chebyshev(v(2, 2)), 0);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let right = v(1, 0);
+        assert_eq!(right.rotate(1), v(0, 1), "clockwise as drawn: right turns down");
+        assert_eq!(right.rotate(2), v(-1, 0));
+        assert_eq!(right.rotate(3), v(0, -1));
+        assert_eq!(right.rotate(4), right);
+        assert_eq!(right.rotate(-1), v(0, -1), "counter-clockwise: right turns up");
+        assert_eq!(v(3, 1).rotate(1), v(-1, 3));
+        assert_eq!(v(3, 1).rotate(-5), v(3, 1).rotate(3));
+    }
+
+    #[test]
+    fn test_direction() {
+        assert_eq!(v(2, 4).direction(), v(1, 2));
+        assert_eq!(v(-6, 9).direction(), v(-2, 3));
+        assert_eq!(v(0, -5).direction(), v(0, -1));
+        assert_eq!(v(3, 7).direction(), v(3, 7));
+        assert_eq!(v(0, 0).direction(), v(0, 0));
+        assert_eq!(gcd(-12, 18), 6);
+        assert_eq!(gcd(0, 0), 0);
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let p = v(5, 5);
+        let n4: Vec<_> = p.neighbors4().collect();
+        assert_eq!(n4, [v(6, 5), v(5, 6), v(4, 5), v(5, 4)]);
+        assert!(n4.iter().all(|&n| p.manhattan(n) == 1));
+
+        let n8: Vec<_> = p.neighbors8().collect();
+        assert_eq!(n8.len(), 8);
+        assert_eq!(n8[..4], n4[..]);
+        assert!(n8.iter().all(|&n| p.chebyshev(n) == 1));
+        for (i, a) in n8.iter().enumerate() {
+            assert!(!n8[i + 1..].contains(a), "{:?} is there twice", a);
+        }
+    }
+}